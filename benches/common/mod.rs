@@ -0,0 +1,46 @@
+//! Events shared by the benchmarks.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use eventline_rust::eventline::eventline::Event;
+use eventline_rust::eventline::field::Field;
+
+#[derive(Clone)]
+pub struct BenchEvent {
+    id: String,
+    time: DateTime<Utc>,
+}
+
+impl Event for BenchEvent {
+    fn get_event_fields(&self) -> Vec<Field> {
+        vec![Field::new("id", self.id.as_str()), Field::new("time", self.time)]
+    }
+
+    fn get_event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn get_event_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn clone_dyn(&self) -> Box<dyn Event + Send> {
+        Box::new(self.clone())
+    }
+}
+
+pub fn event(i: usize, secs: i64) -> Box<dyn Event + Send> {
+    Box::new(BenchEvent {
+        id: format!("event{}", i),
+        time: Utc.timestamp_opt(secs, 0).unwrap(),
+    })
+}
+
+// times are scattered so that updates land all over the order
+pub fn scattered(i: usize) -> i64 {
+    ((i as i64).wrapping_mul(7_919) % 1_000_003) * 60
+}
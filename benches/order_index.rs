@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+
+mod common;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use common::{event, scattered};
+use eventline_rust::eventline::eventline::{Event, Events};
+use eventline_rust::eventline::index::OrderIndex;
+use eventline_rust::eventline::sort::{SortKey, SortOrder, SortSpec};
+
+const SIZES: [usize; 3] = [10_000, 100_000, 1_000_000];
+// events changed between two snapshots in the sync benchmark
+const CHANGED: usize = 100;
+
+fn snapshot(size: usize, shift: i64) -> Events<dyn Event + Send> {
+    let mut events_map = HashMap::with_capacity(size);
+    for i in 0..size {
+        let secs = if i < CHANGED { scattered(i) + shift } else { scattered(i) };
+        events_map.insert(format!("event{}", i), event(i, secs));
+    }
+    Events {
+        global_counter: size as i32,
+        last_update: String::new(),
+        events_map,
+    }
+}
+
+fn by_time() -> SortSpec {
+    SortSpec::by(SortKey::Time, SortOrder::Asc)
+}
+
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_index/build");
+    group.sample_size(10);
+    for size in SIZES {
+        let events = snapshot(size, 0);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &events, |b, events| {
+            b.iter(|| {
+                let mut index = OrderIndex::new(by_time());
+                index.sync(events);
+                black_box(index.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_upsert_one(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_index/upsert_one");
+    for size in SIZES {
+        let mut index = OrderIndex::new(by_time());
+        index.sync(&snapshot(size, 0));
+        let mut n = 0usize;
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                // moves an existing event to a new time
+                n += 1;
+                let i = n % size;
+                let updated = event(i, scattered(n));
+                index.upsert(&format!("event{}", i), updated.as_ref());
+                black_box(index.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_sync_changed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("order_index/sync_snapshot_100_changed");
+    group.sample_size(10);
+    for size in SIZES {
+        // alternating between two snapshots moves CHANGED events every iteration
+        let snapshots = [snapshot(size, 0), snapshot(size, 30)];
+        let mut index = OrderIndex::new(by_time());
+        index.sync(&snapshots[0]);
+        let mut n = 0usize;
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                n += 1;
+                index.sync(&snapshots[n % 2]);
+                black_box(index.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_build, bench_upsert_one, bench_sync_changed);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+
+mod common;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use common::{event, scattered};
+use eventline_rust::eventline::eventline::{EventLine, EventMessage, Events};
+
+const SIZES: [usize; 3] = [10_000, 100_000, 1_000_000];
+
+/// Viewer holding size events, as after a first snapshot.
+fn viewer(size: usize) -> EventLine {
+    let mut events_map = HashMap::with_capacity(size);
+    for i in 0..size {
+        events_map.insert(format!("event{}", i), event(i, scattered(i)));
+    }
+    let mut viewer = EventLine::new(String::from("bench")).unwrap();
+    viewer.apply(EventMessage::Snapshot(Box::new(Events {
+        global_counter: size as i32,
+        last_update: String::new(),
+        events_map,
+    })));
+    viewer
+}
+
+/// One update applied and the listed rows brought up to date, what a frame costs
+/// when a single message arrived since the previous one.
+fn bench_upsert_one(c: &mut Criterion) {
+    let mut group = c.benchmark_group("viewer/upsert_one_and_refresh");
+    group.sample_size(10);
+    for size in SIZES {
+        let mut viewer = viewer(size);
+        let mut n = 0usize;
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                n += 1;
+                let i = n % size;
+                viewer.apply(EventMessage::Upsert(format!("event{}", i), event(i, scattered(n))));
+                black_box(viewer.data_keys().len())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_upsert_one);
+criterion_main!(benches);
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+/// Number of updates received every second over a rolling window.
+#[derive(Debug)]
+pub struct RateTracker {
+    start: Instant,
+    window: usize,
+    // one count per second, the last one is the current second
+    counts: VecDeque<u64>,
+    // seconds since start of the last count
+    last_second: u64,
+}
+
+impl RateTracker {
+    pub fn new(window: Duration) -> Self {
+        RateTracker {
+            start: Instant::now(),
+            window: (window.as_secs() as usize).max(1),
+            counts: VecDeque::from([0]),
+            last_second: 0,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window as u64)
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = (window.as_secs() as usize).max(1);
+        self.advance();
+    }
+
+    /// Counts updates received now.
+    pub fn record(&mut self, updates: u64) {
+        self.advance();
+        if let Some(current) = self.counts.back_mut() {
+            *current += updates;
+        }
+    }
+
+    /// Updates of every second of the window, oldest first, the current second last.
+    pub fn per_second(&self) -> Vec<u64> {
+        // seconds elapsed since the last update had none
+        let idle = (self.start.elapsed().as_secs() - self.last_second).min(self.window as u64) as usize;
+        let mut counts: Vec<u64> = self.counts.iter().copied().chain(std::iter::repeat_n(0, idle)).collect();
+        counts.drain(..counts.len().saturating_sub(self.window));
+        // seconds before start had no update either
+        let missing = self.window - counts.len();
+        counts.splice(0..0, std::iter::repeat_n(0, missing));
+        counts
+    }
+
+    /// Updates received during the last complete second.
+    pub fn last_rate(&self) -> u64 {
+        let counts = self.per_second();
+        counts.len().checked_sub(2).map_or(0, |i| counts[i])
+    }
+
+    /// Adds an empty count for every second elapsed since the last update.
+    fn advance(&mut self) {
+        let now = self.start.elapsed().as_secs();
+        let elapsed = (now - self.last_second).min(self.window as u64 + 1);
+        for _ in 0..elapsed {
+            self.counts.push_back(0);
+        }
+        self.last_second = now;
+        while self.counts.len() > self.window {
+            self.counts.pop_front();
+        }
+    }
+}
+
+/// How fast the viewer keeps up: messages applied and frames drawn every second,
+/// and the time a frame takes to draw.
+#[derive(Debug)]
+pub struct LoopStats {
+    applied: RateTracker,
+    frames: RateTracker,
+    // recent frames weigh more, so it follows changes of load within a few frames
+    frame_time: Duration,
+}
+
+impl Default for LoopStats {
+    fn default() -> Self {
+        LoopStats {
+            applied: RateTracker::new(Duration::from_secs(2)),
+            frames: RateTracker::new(Duration::from_secs(2)),
+            frame_time: Duration::ZERO,
+        }
+    }
+}
+
+impl LoopStats {
+    pub fn record_applied(&mut self, messages: u64) {
+        self.applied.record(messages);
+    }
+
+    pub fn record_frame(&mut self, took: Duration) {
+        self.frames.record(1);
+        self.frame_time = if self.frame_time.is_zero() { took } else { (self.frame_time * 7 + took) / 8 };
+    }
+
+    /// Messages applied during the last complete second.
+    pub fn apply_rate(&self) -> u64 {
+        self.applied.last_rate()
+    }
+
+    /// Frames drawn during the last complete second.
+    pub fn frame_rate(&self) -> u64 {
+        self.frames.last_rate()
+    }
+
+    /// Average time spent drawing a frame.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+}
+
+/// Averages groups of consecutive values so that at most width remain,
+/// the last group ending with the last value.
+pub fn squeeze(values: &[u64], width: usize) -> Vec<u64> {
+    if width == 0 || values.len() <= width {
+        return values.to_vec();
+    }
+    let group = values.len().div_ceil(width);
+    values
+        .rchunks(group)
+        .rev()
+        .map(|chunk| chunk.iter().sum::<u64>().div_ceil(chunk.len() as u64))
+        .collect()
+}
+
+/// Bucket sizes tried in order, the smallest one fitting the range is used.
+const BUCKETS: [(i64, &str); 16] = [
+    (1, "1s"),
+    (5, "5s"),
+    (15, "15s"),
+    (60, "1m"),
+    (5 * 60, "5m"),
+    (15 * 60, "15m"),
+    (3600, "1h"),
+    (6 * 3600, "6h"),
+    (86400, "1d"),
+    (7 * 86400, "1w"),
+    (30 * 86400, "30d"),
+    (91 * 86400, "91d"),
+    (365 * 86400, "1y"),
+    (5 * 365 * 86400, "5y"),
+    (10 * 365 * 86400, "10y"),
+    (100 * 365 * 86400, "100y"),
+];
+
+/// Number of events in every bucket of time between the earliest and the latest one.
+#[derive(Debug, Clone)]
+pub struct TimeHistogram {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Size of a bucket, like "1h".
+    pub bucket: &'static str,
+    pub counts: Vec<u64>,
+}
+
+impl TimeHistogram {
+    /// Histogram of times (Unix milliseconds, with the number of events at each) in at
+    /// most max_buckets buckets, the bucket size adapting to the range of the times.
+    /// None without times.
+    pub fn new(times: &BTreeMap<i64, usize>, max_buckets: usize) -> Option<Self> {
+        let min = times.first_key_value()?.0.div_euclid(1000);
+        let max = times.last_key_value()?.0.div_euclid(1000);
+        let max_buckets = max_buckets.max(1) as i64;
+        let (size, bucket) = BUCKETS
+            .iter()
+            .copied()
+            // aligning the buckets may need one more
+            .find(|(size, _)| (max - min) / size + 2 <= max_buckets)
+            .unwrap_or(BUCKETS[BUCKETS.len() - 1]);
+        // buckets start on a multiple of their size, like a whole hour
+        let first = min.div_euclid(size);
+        let mut counts = vec![0; (max.div_euclid(size) - first + 1) as usize];
+        for (time, count) in times {
+            counts[(time.div_euclid(1000).div_euclid(size) - first) as usize] += *count as u64;
+        }
+        Some(TimeHistogram {
+            start: DateTime::from_timestamp(min, 0)?,
+            end: DateTime::from_timestamp(max, 0)?,
+            bucket,
+            counts,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::eventline::Event;
+
+/// How an event changed in the latest update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// Recent changes of the events, kept for a while so the viewer can show them.
+/// Removed events are kept here until their change expires, so their row can
+/// still be drawn.
+pub struct ChangeTracker {
+    duration: Duration,
+    changes: HashMap<String, (ChangeKind, Instant)>,
+    removed: HashMap<String, Box<dyn Event + Send>>,
+}
+
+impl ChangeTracker {
+    pub fn new(duration: Duration) -> Self {
+        ChangeTracker {
+            duration,
+            changes: HashMap::new(),
+            removed: HashMap::new(),
+        }
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    /// Records that the event under key was added or modified.
+    /// An event coming back before its removal expired is no longer removed.
+    pub fn record(&mut self, key: &str, kind: ChangeKind) {
+        self.removed.remove(key);
+        self.changes.insert(key.to_string(), (kind, Instant::now()));
+    }
+
+    /// Keeps a removed event around until its change expires.
+    pub fn record_removed(&mut self, key: String, event: Box<dyn Event + Send>) {
+        self.changes.insert(key.clone(), (ChangeKind::Removed, Instant::now()));
+        self.removed.insert(key, event);
+    }
+
+    /// Kind of the change of key and how far it faded, from 0 (just changed) to 1.
+    pub fn get(&self, key: &str) -> Option<(ChangeKind, f32)> {
+        let (kind, at) = self.changes.get(key)?;
+        let faded = at.elapsed().as_secs_f32() / self.duration.as_secs_f32().max(f32::EPSILON);
+        Some((*kind, faded.min(1.0)))
+    }
+
+    /// True when no row is highlighted.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// A removed event still displayed.
+    pub fn removed(&self, key: &str) -> Option<&(dyn Event + Send)> {
+        self.removed.get(key).map(|e| e.as_ref())
+    }
+
+    pub fn removed_events(&self) -> impl Iterator<Item = (&String, &Box<dyn Event + Send>)> {
+        self.removed.iter()
+    }
+
+    /// Forgets the expired changes and returns the keys of the removed
+    /// events that should now disappear.
+    pub fn expire(&mut self) -> Vec<String> {
+        let duration = self.duration;
+        let mut gone = Vec::new();
+        self.changes.retain(|key, (kind, at)| {
+            let keep = at.elapsed() < duration;
+            if !keep && *kind == ChangeKind::Removed {
+                gone.push(key.clone());
+            }
+            keep
+        });
+        for key in &gone {
+            self.removed.remove(key);
+        }
+        gone
+    }
+}
+
+/// True when two versions of an event would be displayed the same way. Only the typed
+/// values are compared, the presentation is made from them and is not formatted here.
+pub fn same_event(a: &(dyn Event + Send), b: &(dyn Event + Send)) -> bool {
+    a.get_event_time() == b.get_event_time()
+        && a.get_event_severity() == b.get_event_severity()
+        && a.get_event_fields() == b.get_event_fields()
+}
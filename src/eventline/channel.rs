@@ -0,0 +1,411 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Notify, watch};
+
+use super::eventline::{Event, EventMessage};
+
+/// What a bounded channel does with a message sent while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The producer waits until the viewer takes a message.
+    Block,
+    /// The message being sent is dropped.
+    DropNewest,
+    /// The oldest queued message is dropped to make room.
+    DropOldest,
+    /// The queued messages and the new one are merged: deltas are folded into
+    /// the latest snapshot, or into one batch of upserts followed by the removals.
+    /// The queue may briefly exceed its capacity when many distinct removals are pending.
+    Coalesce,
+}
+
+/// Counters of a channel, shown in the viewer header.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelStats {
+    pub capacity: Option<usize>,
+    pub policy: OverflowPolicy,
+    pub queued: usize,
+    pub dropped: u64,
+    pub coalesced: u64,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<EventMessage>>,
+    // None for an unbounded channel
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    // wakes the receiver when a message is queued or the last sender leaves
+    readable: Notify,
+    // wakes blocked senders when a message is taken or the receiver leaves
+    writable: Notify,
+    senders: AtomicUsize,
+    receiver_closed: AtomicBool,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// Sending half of the event channel. Cloning it adds a producer,
+/// the receiver sees the channel closed once every sender is dropped.
+pub struct EventSender {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half of the event channel, owned by the EventLine.
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+/// Creates a channel holding at most capacity messages (None for no limit),
+/// applying policy when a message is sent to a full channel.
+pub fn event_channel(capacity: Option<usize>, policy: OverflowPolicy) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::new()),
+        capacity: capacity.map(|c| c.max(1)),
+        policy,
+        readable: Notify::new(),
+        writable: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receiver_closed: AtomicBool::new(false),
+        dropped: AtomicU64::new(0),
+        coalesced: AtomicU64::new(0),
+    });
+    (
+        EventSender {
+            shared: shared.clone(),
+        },
+        EventReceiver { shared },
+    )
+}
+
+impl EventSender {
+    /// Queues message, applying the overflow policy when the channel is full.
+    /// Fails only when the viewer is gone.
+    pub async fn send(&self, message: EventMessage) -> Result<(), String> {
+        let shared = &self.shared;
+        let mut message = Some(message);
+        loop {
+            let mut writable = pin!(shared.writable.notified());
+            writable.as_mut().enable();
+            {
+                let mut queue = shared.queue.lock().unwrap();
+                if shared.receiver_closed.load(Ordering::Acquire) {
+                    return Err(String::from("event channel closed"));
+                }
+                let full = shared.capacity.is_some_and(|capacity| queue.len() >= capacity);
+                if !full {
+                    queue.push_back(message.take().unwrap());
+                } else {
+                    match shared.policy {
+                        // wait below for the receiver to make room
+                        OverflowPolicy::Block => {}
+                        OverflowPolicy::DropNewest => {
+                            shared.dropped.fetch_add(1, Ordering::Relaxed);
+                            return Ok(());
+                        }
+                        OverflowPolicy::DropOldest => {
+                            queue.pop_front();
+                            queue.push_back(message.take().unwrap());
+                            shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        OverflowPolicy::Coalesce => {
+                            queue.push_back(message.take().unwrap());
+                            let merged = coalesce(&mut queue);
+                            shared.coalesced.fetch_add(merged as u64, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+            if message.is_none() {
+                shared.readable.notify_one();
+                return Ok(());
+            }
+            writable.await;
+        }
+    }
+
+    /// True once the viewer dropped the receiver.
+    pub fn is_closed(&self) -> bool {
+        self.shared.receiver_closed.load(Ordering::Acquire)
+    }
+}
+
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        EventSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+impl EventReceiver {
+    /// Waits for the next message. Returns None once the queue is empty
+    /// and every sender has been dropped.
+    pub async fn recv(&mut self) -> Option<EventMessage> {
+        let shared = self.shared.clone();
+        loop {
+            let mut readable = pin!(shared.readable.notified());
+            readable.as_mut().enable();
+            if let Some(message) = self.try_recv() {
+                return Some(message);
+            }
+            if shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            readable.await;
+        }
+    }
+
+    /// Takes the next message if one is queued.
+    pub fn try_recv(&mut self) -> Option<EventMessage> {
+        let message = self.shared.queue.lock().unwrap().pop_front();
+        if message.is_some() {
+            self.shared.writable.notify_one();
+        }
+        message
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        let shared = &self.shared;
+        ChannelStats {
+            capacity: shared.capacity,
+            policy: shared.policy,
+            queued: shared.queue.lock().unwrap().len(),
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            coalesced: shared.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.receiver_closed.store(true, Ordering::Release);
+        self.shared.writable.notify_waiters();
+    }
+}
+
+/// Viewer side of the shutdown: handles learn the viewer ended when it is
+/// triggered, or when it is dropped along with the viewer.
+#[derive(Debug)]
+pub struct Shutdown {
+    ended: watch::Sender<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            ended: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn trigger(&self) {
+        self.ended.send_replace(true);
+    }
+
+    pub fn handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            ended: self.ended.subscribe(),
+        }
+    }
+}
+
+/// Lets a producer stop once the viewer ended, however it ended.
+/// Every clone is told.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    ended: watch::Receiver<bool>,
+}
+
+impl ShutdownHandle {
+    pub fn is_shutdown(&self) -> bool {
+        *self.ended.borrow() || self.ended.has_changed().is_err()
+    }
+
+    /// Waits until the viewer ended.
+    pub async fn wait(&mut self) {
+        // an error means the viewer was dropped, which ends it too
+        let _ = self.ended.wait_for(|ended| *ended).await;
+    }
+}
+
+/// Replaces the queued messages with the shortest sequence having the same effect.
+/// Returns how many messages were merged away.
+fn coalesce(queue: &mut VecDeque<EventMessage>) -> usize {
+    let before = queue.len();
+    // latest Snapshot or Clear, the deltas before it no longer matter
+    let mut base: Option<EventMessage> = None;
+    // last change of every key, with a sequence number to keep the order of the updates
+    let mut changes: HashMap<String, (usize, Option<Box<dyn Event + Send>>)> = HashMap::new();
+    // statuses are kept, only the last one of every source
+    let mut statuses: Vec<(String, String)> = Vec::new();
+    let mut seq = 0;
+    let mut change = |changes: &mut HashMap<_, _>, key: String, event| {
+        seq += 1;
+        changes.insert(key, (seq, event));
+    };
+
+    for message in queue.drain(..) {
+        match message {
+            EventMessage::Snapshot(_) | EventMessage::Clear => {
+                base = Some(message);
+                changes.clear();
+            }
+            EventMessage::Upsert(key, event) => change(&mut changes, key, Some(event)),
+            EventMessage::Batch(events) => {
+                for (key, event) in events {
+                    change(&mut changes, key, Some(event));
+                }
+            }
+            EventMessage::Remove(key) => change(&mut changes, key, None),
+            EventMessage::Status(source, status) => match statuses.iter_mut().find(|(name, _)| *name == source) {
+                Some((_, current)) => *current = status,
+                None => statuses.push((source, status)),
+            },
+        }
+    }
+
+    let mut changes: Vec<_> = changes.into_iter().collect();
+    changes.sort_by_key(|(_, (seq, _))| *seq);
+
+    match base {
+        Some(EventMessage::Snapshot(mut events)) => {
+            // fold the deltas into the snapshot, counting upserts like the viewer does
+            for (key, (_, event)) in changes {
+                match event {
+                    Some(event) => {
+                        events.global_counter += 1;
+                        events.last_update = key.clone();
+                        events.events_map.insert(key, event);
+                    }
+                    None => {
+                        events.events_map.remove(&key);
+                    }
+                }
+            }
+            queue.push_back(EventMessage::Snapshot(events));
+        }
+        base => {
+            let cleared = base.is_some();
+            if let Some(clear) = base {
+                queue.push_back(clear);
+            }
+            let mut upserts = Vec::new();
+            let mut removals = Vec::new();
+            for (key, (_, event)) in changes {
+                match event {
+                    Some(event) => upserts.push((key, event)),
+                    None => removals.push(key),
+                }
+            }
+            if !upserts.is_empty() {
+                queue.push_back(EventMessage::Batch(upserts));
+            }
+            // nothing left to remove after a clear
+            if !cleared {
+                queue.extend(removals.into_iter().map(EventMessage::Remove));
+            }
+        }
+    }
+    queue.extend(statuses.into_iter().map(|(source, status)| EventMessage::Status(source, status)));
+    before - queue.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::eventline::Events;
+    use crate::eventline::source::LineEvent;
+
+    fn upsert(key: &str) -> EventMessage {
+        EventMessage::Upsert(key.to_string(), Box::new(LineEvent::new("test", 0, key.to_string())))
+    }
+
+    fn remove(key: &str) -> EventMessage {
+        EventMessage::Remove(key.to_string())
+    }
+
+    fn status(status: &str) -> EventMessage {
+        EventMessage::Status(String::from("test"), status.to_string())
+    }
+
+    /// Short text of the messages, to compare queues.
+    fn describe(queue: &VecDeque<EventMessage>) -> Vec<String> {
+        queue
+            .iter()
+            .map(|message| match message {
+                EventMessage::Upsert(key, _) => format!("upsert {}", key),
+                EventMessage::Remove(key) => format!("remove {}", key),
+                EventMessage::Batch(events) => {
+                    let keys: Vec<&str> = events.iter().map(|(key, _)| key.as_str()).collect();
+                    format!("batch {}", keys.join(" "))
+                }
+                EventMessage::Clear => String::from("clear"),
+                EventMessage::Snapshot(events) => {
+                    let mut keys: Vec<&str> = events.events_map.keys().map(String::as_str).collect();
+                    keys.sort();
+                    format!("snapshot {} ({} {})", keys.join(" "), events.global_counter, events.last_update)
+                }
+                EventMessage::Status(source, status) => format!("status {} {}", source, status),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deltas_fold_into_the_snapshot() {
+        let mut snapshot = Events {
+            global_counter: 10,
+            ..Default::default()
+        };
+        for key in ["a", "b"] {
+            snapshot.events_map.insert(key.to_string(), Box::new(LineEvent::new("test", 0, key.to_string())) as _);
+        }
+        let mut queue = VecDeque::from([
+            upsert("old"),
+            EventMessage::Snapshot(Box::new(snapshot)),
+            upsert("c"),
+            remove("a"),
+            status("reading"),
+            upsert("d"),
+            status("closed"),
+        ]);
+        assert_eq!(coalesce(&mut queue), 5);
+        assert_eq!(describe(&queue), ["snapshot b c d (12 d)", "status test closed"]);
+    }
+
+    #[test]
+    fn clear_drops_the_removals() {
+        let mut queue =
+            VecDeque::from([upsert("a"), remove("b"), EventMessage::Clear, upsert("c"), remove("c"), remove("d"), upsert("e")]);
+        assert_eq!(coalesce(&mut queue), 5);
+        assert_eq!(describe(&queue), ["clear", "batch e"]);
+    }
+
+    #[test]
+    fn deltas_keep_the_last_change_of_every_key() {
+        let mut queue = VecDeque::from([
+            upsert("a"),
+            EventMessage::Batch(vec![
+                (String::from("b"), Box::new(LineEvent::new("test", 0, String::from("b"))) as _),
+                (String::from("c"), Box::new(LineEvent::new("test", 0, String::from("c"))) as _),
+            ]),
+            remove("a"),
+            remove("x"),
+            upsert("b"),
+        ]);
+        assert_eq!(coalesce(&mut queue), 2);
+        assert_eq!(describe(&queue), ["batch c b", "remove a", "remove x"]);
+    }
+}
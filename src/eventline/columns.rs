@@ -0,0 +1,88 @@
+use super::field::Field;
+
+/// A column of the table view, named after an event field.
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub visible: bool,
+}
+
+/// Ordered set of table columns discovered from the event fields,
+/// with a cursor used to hide and reorder them from the keyboard.
+#[derive(Debug, Default)]
+pub struct Columns {
+    columns: Vec<Column>,
+    // index in columns, always on a visible column when there is one
+    cursor: usize,
+}
+
+impl Columns {
+    /// Adds a column for every field name not seen yet, in order of first appearance.
+    pub fn discover(&mut self, fields: &[Field]) {
+        for field in fields {
+            if !self.columns.iter().any(|c| c.name == field.name) {
+                self.columns.push(Column {
+                    name: field.name.clone(),
+                    visible: true,
+                });
+            }
+        }
+    }
+
+    /// Visible columns in display order.
+    pub fn visible(&self) -> impl Iterator<Item = &Column> {
+        self.columns.iter().filter(|c| c.visible)
+    }
+
+    /// Name of the column under the cursor.
+    pub fn current(&self) -> Option<&str> {
+        self.columns
+            .get(self.cursor)
+            .filter(|c| c.visible)
+            .map(|c| c.name.as_str())
+    }
+
+    /// Moves the cursor to the next (delta > 0) or previous visible column.
+    pub fn move_cursor(&mut self, delta: isize) {
+        if let Some(index) = self.neighbour(delta) {
+            self.cursor = index;
+        }
+    }
+
+    /// Hides the column under the cursor, keeping at least one column visible.
+    pub fn hide_current(&mut self) {
+        if self.visible().count() <= 1 {
+            return;
+        }
+        let next = self.neighbour(1).or_else(|| self.neighbour(-1));
+        if let Some(column) = self.columns.get_mut(self.cursor) {
+            column.visible = false;
+        }
+        if let Some(index) = next {
+            self.cursor = index;
+        }
+    }
+
+    pub fn show_all(&mut self) {
+        for column in self.columns.iter_mut() {
+            column.visible = true;
+        }
+    }
+
+    /// Swaps the column under the cursor with its visible neighbour, the cursor follows it.
+    pub fn shift_current(&mut self, delta: isize) {
+        if let Some(index) = self.neighbour(delta) {
+            self.columns.swap(self.cursor, index);
+            self.cursor = index;
+        }
+    }
+
+    /// Index of the nearest visible column in the direction of delta.
+    fn neighbour(&self, delta: isize) -> Option<usize> {
+        if delta > 0 {
+            (self.cursor + 1..self.columns.len()).find(|&i| self.columns[i].visible)
+        } else {
+            (0..self.cursor).rev().find(|&i| self.columns[i].visible)
+        }
+    }
+}
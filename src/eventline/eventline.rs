@@ -1,33 +1,75 @@
+use super::activity::{LoopStats, RateTracker, TimeHistogram};
+use super::changes::{self, ChangeKind, ChangeTracker};
+use super::channel::{self, ChannelStats, EventReceiver, EventSender, OverflowPolicy, Shutdown, ShutdownHandle};
+use super::columns::Columns;
+use super::facets::Facets;
+use super::field::{Field, FieldValue};
+use super::filter::Filter;
+use super::index::OrderIndex;
+use super::listed::ListedKeys;
+use super::rows::{RowCache, RowText};
+use super::search::{Search, SearchMode};
+use super::severity::Severity;
+use super::sort::{SortKey, SortPosition, SortSpec};
+use super::terminal::{Signals, TerminalGuard};
+use super::timeline::{Timeline, TimelineColor};
 use super::ui;
 
-use std::{
-    cell::RefCell,
-    time::Duration,
-};
-
-use color_eyre::Result;
-use crossterm::event::{KeyCode, poll, Event as CEvent};
-use ratatui::{
-    DefaultTerminal,
-};
-
-use tokio::sync::mpsc::{
-    unbounded_channel,
-    UnboundedReceiver,
-    UnboundedSender,
-    error::TryRecvError,
-};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossterm::event::{Event as CEvent, EventStream};
+use futures::StreamExt;
+
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+// How long added and changed rows stay highlighted, and removed rows stay listed
+pub const DEFAULT_CHANGE_HIGHLIGHT: Duration = Duration::from_secs(3);
+
+// Time between redraws when nothing happens, for the clock and the rate in the header
+const IDLE_TICK: Duration = Duration::from_secs(1);
+
+// Time between redraws while changed rows fade
+const FADE_TICK: Duration = Duration::from_millis(100);
 
-// Constants for sorting order
-pub const ASC: i32 = 0;
-pub const DESC: i32 = 1;
+// Frames drawn at most every second, updates arriving in between are shown together
+pub const DEFAULT_MAX_FPS: u32 = 30;
+
+// Longest time spent applying queued messages before the keyboard and the screen get a turn
+const APPLY_BUDGET: Duration = Duration::from_millis(20);
+
+// Span of the update rate shown in the header
+pub const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(5 * 60);
 
 pub trait Event {
-    fn get_event_presentation(&self) -> String;
+    /// Named, typed fields of the event in display order.
+    fn get_event_fields(&self) -> Vec<Field>;
+    /// One line summary of the event. The default joins the fields as `name: value`.
+    /// Made from the fields, the time and the severity: an update changing none of them
+    /// is taken as the same event and the row is not formatted again.
+    fn get_event_presentation(&self) -> String {
+        self.get_event_fields()
+            .iter()
+            .map(|field| format!("{}: {}", field.name, field.value))
+            .collect::<Vec<_>>()
+            .join(" - ")
+    }
+    /// Value of the field called name, if the event has one.
+    fn get_event_field(&self, name: &str) -> Option<FieldValue> {
+        self.get_event_fields()
+            .into_iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value)
+    }
     fn get_event_time(&self) -> chrono::DateTime<chrono::Utc>;
     fn get_event_id(&self) -> String;
+    /// How important the event is, info unless the event tells otherwise.
+    fn get_event_severity(&self) -> Severity {
+        Severity::Info
+    }
     fn as_any(&self) -> &dyn std::any::Any;
     fn clone_dyn(&self) -> Box<dyn Event + Send>;
 }
@@ -38,7 +80,17 @@ pub struct Events<E: ?Sized = dyn Event> {
     pub events_map: std::collections::HashMap<String, Box<E>>
 }
 
-impl<E: ?Sized + Clone> Clone for Events<E>
+impl<E: ?Sized> Default for Events<E> {
+    fn default() -> Self {
+        Events {
+            global_counter: 0,
+            last_update: String::new(),
+            events_map: std::collections::HashMap::new(),
+        }
+    }
+}
+
+impl<E: ?Sized> Clone for Events<E>
 where
     Box<E>: Clone,
 {
@@ -51,40 +103,177 @@ where
     }
 }
 
+impl Events<dyn Event + Send> {
+    /// Deep copy of the events, each one duplicated through clone_dyn.
+    pub fn clone_events(&self) -> Self {
+        Events {
+            global_counter: self.global_counter,
+            last_update: self.last_update.clone(),
+            events_map: self
+                .events_map
+                .iter()
+                .map(|(key, event)| (key.clone(), event.clone_dyn()))
+                .collect(),
+        }
+    }
+}
+
+/// Change sent by a producer to the viewer. Producers only send what changed,
+/// the viewer keeps the resulting Events itself.
+pub enum EventMessage {
+    /// Inserts or replaces the event stored under the key.
+    Upsert(String, Box<dyn Event + Send>),
+    /// Removes the event stored under the key.
+    Remove(String),
+    /// Several upserts applied together.
+    Batch(Vec<(String, Box<dyn Event + Send>)>),
+    /// Removes every event.
+    Clear,
+    /// Replaces every event, counters included.
+    Snapshot(Box<Events<dyn Event + Send>>),
+    /// State of a named source of events, like ("stdin", "closed"), shown in the header.
+    Status(String, String),
+}
+
+impl EventMessage {
+    /// Upsert keyed by the id of the event.
+    pub fn upsert(event: Box<dyn Event + Send>) -> Self {
+        EventMessage::Upsert(event.get_event_id(), event)
+    }
+}
+
+/// How the events are laid out in the viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    /// One presentation line per event.
+    List,
+    /// One column per event field.
+    Table,
+}
+
 pub struct EventLine {
-    event_receiver: Option<UnboundedReceiver<Box<Events<dyn Event + Send>>>>,
+    event_receiver: Option<EventReceiver>,
     events_data: Option<Events<dyn Event + Send>>,
     title: String,
-    data_list: Vec<String>,
-    term: RefCell<DefaultTerminal>,
+    // keys of the listed events in display order
+    data_keys: ListedKeys,
+    // events passing the severity threshold and the filter, with how they are listed
+    filtered_rows: HashMap<String, FilteredRow>,
+    // the filter, the search, the threshold or the sort changed, every event is listed again
+    view_stale: bool,
+    // events changed since the listed rows were updated, listed again one at a time
+    stale_keys: HashSet<String>,
+    // text of the rows drawn so far
+    rows: RowCache,
+    // the selection is tracked by key so it survives a new snapshot
+    selected_key: Option<String>,
+    selected: Option<usize>,
+    // the detail pane shows every field of the selected event
+    detail_open: bool,
+    view_mode: ViewMode,
+    columns: Columns,
+    // keys in display order, updated as snapshots arrive
+    index: OrderIndex,
+    // keys are read as prompt text instead of commands while editing
+    input_mode: ui::InputMode,
+    prompt: String,
+    search: Option<Search>,
+    // mode used for the query typed in the prompt
+    search_mode: SearchMode,
+    // search in effect before the prompt opened, restored on cancel
+    saved_search: Option<Search>,
+    // expression typed after :filter, every listed event matches it
+    filter: Option<Filter>,
+    saved_filter: Option<Filter>,
+    filtered_count: usize,
+    // row of the most recently updated event, if it is listed
+    last_update_index: Option<usize>,
+    // keeps the last updated event selected as updates arrive, like tail -f
+    follow: bool,
+    // events less severe than this are not listed
+    min_severity: Severity,
+    // number of stored events of every severity, indexed like Severity::ALL
+    severity_counts: [usize; Severity::ALL.len()],
+    // updates received every second, for the header sparkline
+    rate: RateTracker,
+    max_fps: u32,
+    // messages applied, frames drawn and time per frame, shown in the header
+    loop_stats: LoopStats,
+    // number of listed events at every time in Unix milliseconds, for the header histogram
+    listed_times: BTreeMap<i64, usize>,
+    // earliest and latest of listed_times
+    listed_range: Option<(i64, i64)>,
+    timeline: Timeline,
+    // value counts of the field shown in the facet panel
+    facets: Facets,
+    // last state reported by every source, in order of first report
+    sources: Vec<(String, String)>,
+    // recently added, modified and removed events, removed ones stay listed until they expire
+    changes: ChangeTracker,
+    // problem with what is typed in the prompt, shown next to it
+    prompt_error: Option<String>,
+    // last error reading the keyboard, shown in the status line
+    last_error: Option<String>,
+    // only matching rows are listed, otherwise matches are highlighted
+    filter_matches: bool,
+    match_count: usize,
     ui_handler: ui::UI,
     shutdown_tx: Option<oneshot::Sender<bool>>,
+    // tells the shutdown handles when the viewer ends
+    shutdown: Shutdown,
+    // every sender was dropped, nothing more will arrive
+    disconnected: bool,
+    // ends the viewer when the producers are gone instead of showing their last state
+    exit_on_disconnect: bool,
 }
 
 impl EventLine {
-    fn check_for_keypress(&self) -> Result<Option<KeyCode>> {
-        if poll(std::time::Duration::from_millis(100))? {
-            if let CEvent::Key(key) = crossterm::event::read()? {
-                return Ok(Some(key.code));
-            }
-        }
-        Ok(None)
-    }
-
     pub fn new(s: String) -> Result<Self, String> {
-        match color_eyre::install() {
-            Ok(_) => {},
-            Err(e) => return Err(format!("error: {}", e)),
-        }
-
         Ok(EventLine {
             event_receiver: None,
             events_data: None,
             title: s,
-            data_list: Vec::new(),
-            term: RefCell::new(ratatui::init()),
+            view_stale: false,
+            stale_keys: HashSet::new(),
+            rows: RowCache::default(),
+            data_keys: ListedKeys::default(),
+            filtered_rows: HashMap::new(),
+            selected_key: None,
+            selected: None,
+            detail_open: false,
+            view_mode: ViewMode::Table,
+            columns: Columns::default(),
+            index: OrderIndex::default(),
+            input_mode: ui::InputMode::Normal,
+            prompt: String::new(),
+            search: None,
+            search_mode: SearchMode::IgnoreCase,
+            saved_search: None,
+            filter: None,
+            saved_filter: None,
+            filtered_count: 0,
+            last_update_index: None,
+            follow: false,
+            min_severity: Severity::Trace,
+            severity_counts: [0; Severity::ALL.len()],
+            rate: RateTracker::new(DEFAULT_RATE_WINDOW),
+            max_fps: DEFAULT_MAX_FPS,
+            loop_stats: LoopStats::default(),
+            listed_times: BTreeMap::new(),
+            listed_range: None,
+            timeline: Timeline::default(),
+            facets: Facets::default(),
+            sources: Vec::new(),
+            changes: ChangeTracker::new(DEFAULT_CHANGE_HIGHLIGHT),
+            prompt_error: None,
+            last_error: None,
+            filter_matches: true,
+            match_count: 0,
             ui_handler: ui::UI::new(),
             shutdown_tx: None,
+            shutdown: Shutdown::default(),
+            disconnected: false,
+            exit_on_disconnect: false,
         })
     }
 
@@ -93,25 +282,203 @@ impl EventLine {
         self
     }
 
+    /// Ends the viewer once every producer dropped its sender, instead of
+    /// showing the last state until the user quits.
+    pub fn with_exit_on_disconnect(&mut self, exit: bool) -> &Self {
+        self.exit_on_disconnect = exit;
+        self
+    }
+
+    /// Handle resolving when the viewer ended, for producers to stop.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.handle()
+    }
+
+    /// Sets how many frames are drawn at most every second.
+    pub fn with_max_fps(&mut self, max_fps: u32) -> &Self {
+        self.max_fps = max_fps.max(1);
+        self
+    }
+
+    /// Sets the span of the update rate shown in the header.
+    pub fn with_rate_window(&mut self, window: Duration) -> &Self {
+        self.rate.set_window(window);
+        self
+    }
+
+    /// Sets how long changed rows stay highlighted and removed rows stay listed.
+    pub fn with_change_highlight(&mut self, duration: Duration) -> &Self {
+        self.changes.set_duration(duration);
+        self
+    }
+
     pub fn title(&self) -> &str {
         &self.title
     }
 
-    pub fn data_list(&self) -> &Vec<String> {
-        &self.data_list
+    /// Keys of the listed events in display order.
+    pub fn data_keys(&self) -> &ListedKeys {
+        &self.data_keys
+    }
+
+    /// Text of the event under key, formatted when first asked for.
+    pub fn row(&self, key: &str) -> Option<Arc<RowText>> {
+        self.event(key).map(|event| self.rows.get(key, event))
+    }
+
+    /// Event stored under key in the current snapshot.
+    pub fn event(&self, key: &str) -> Option<&(dyn Event + Send)> {
+        match self.events_data.as_ref()?.events_map.get(key) {
+            Some(event) => Some(event.as_ref()),
+            None => self.changes.removed(key),
+        }
+    }
+
+    pub fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    /// Number of stored events of severity.
+    pub fn severity_count(&self, severity: Severity) -> usize {
+        self.severity_counts[severity.index()]
+    }
+
+    /// Updates received every second over the rate window.
+    pub fn rate(&self) -> &RateTracker {
+        &self.rate
+    }
+
+    /// True once every producer is gone.
+    pub fn disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    pub fn max_fps(&self) -> u32 {
+        self.max_fps
+    }
+
+    /// Messages applied, frames drawn and time per frame.
+    pub fn loop_stats(&self) -> &LoopStats {
+        &self.loop_stats
+    }
+
+    /// Times of the listed events in at most max_buckets buckets.
+    pub fn time_histogram(&self, max_buckets: usize) -> Option<TimeHistogram> {
+        TimeHistogram::new(&self.listed_times, max_buckets)
+    }
+
+    pub fn facets(&self) -> &Facets {
+        &self.facets
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    /// Seconds since the epoch of the earliest and latest listed events.
+    pub fn time_range(&self) -> Option<(f64, f64)> {
+        self.listed_range
+            .map(|(start, end)| (start as f64 / 1000.0, end as f64 / 1000.0))
+    }
+
+    /// Sources of events with the last state they reported.
+    pub fn sources(&self) -> &[(String, String)] {
+        &self.sources
+    }
+
+    /// Recent change of the event under key and how far its highlight faded, from 0 to 1.
+    pub fn change(&self, key: &str) -> Option<(ChangeKind, f32)> {
+        self.changes.get(key)
+    }
+
+    pub fn view_mode(&self) -> ViewMode {
+        self.view_mode
+    }
+
+    pub fn columns(&self) -> &Columns {
+        &self.columns
+    }
+
+    pub fn sort(&self) -> &SortSpec {
+        self.index.spec()
+    }
+
+    pub fn input_mode(&self) -> ui::InputMode {
+        self.input_mode
+    }
+
+    /// Text typed in the prompt so far.
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    pub fn search_mode(&self) -> SearchMode {
+        self.search_mode
+    }
+
+    pub fn prompt_error(&self) -> Option<&str> {
+        self.prompt_error.as_deref()
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn last_update_index(&self) -> Option<usize> {
+        self.last_update_index
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    /// Number of events passing the filter and the severity threshold.
+    pub fn filtered_count(&self) -> usize {
+        self.filtered_count
+    }
+
+    pub fn filter_matches(&self) -> bool {
+        self.filter_matches
+    }
+
+    /// Number of events matching the search, filtered out or not.
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+
+    /// Index in data_keys of the selected row, if any.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn detail_open(&self) -> bool {
+        self.detail_open
+    }
+
+    /// The event behind the selected row, if it is still in the map.
+    pub fn selected_event(&self) -> Option<&(dyn Event + Send)> {
+        self.event(self.selected_key.as_ref()?)
     }
 
     pub fn global_counter(&self) -> i32 {
         if let Some(events) = self.events_data.as_ref() {
             return events.global_counter;
         }
-        return 0
+        0
     }
     pub fn last_update(&self) -> String {
         if let Some(events) = self.events_data.as_ref() {
             return events.last_update.clone();
         }
-        return "".to_string();
+        "".to_string()
     }
 
     pub fn events_map_size(&self) -> usize {
@@ -120,187 +487,895 @@ impl EventLine {
         }
         self.events_data.as_ref().unwrap().events_map.len()
     }
+    /// Counters of the event channel, once it is created.
+    pub fn channel_stats(&self) -> Option<ChannelStats> {
+        self.event_receiver.as_ref().map(|receiver| receiver.stats())
+    }
+
     // Create a channel for event communication and return the sender
+    pub fn create_event_channel(&mut self) -> EventSender {
+        let (sender, receiver) = channel::event_channel(None, OverflowPolicy::Block);
+        self.event_receiver = Some(receiver);
+        sender
+    }
 
-    // Alternative method that creates a bounded channel with specified capacity
-    pub fn create_event_channel(&mut self) -> UnboundedSender<Box<Events<dyn Event + Send>>> {
-        // Using crossbeam's bounded channel
-        let (sender, receiver) = unbounded_channel();
+    // Alternative method that creates a bounded channel with specified capacity,
+    // policy decides what happens to messages sent while it is full
+    pub fn create_bounded_event_channel(&mut self, capacity: usize, policy: OverflowPolicy) -> EventSender {
+        let (sender, receiver) = channel::event_channel(Some(capacity), policy);
         self.event_receiver = Some(receiver);
         sender
     }
 
     // Start the event processing in a thread and return a join handle
-    pub fn start_in_thread(mut self) -> JoinHandle<()> {
-        let handle = tokio::spawn(async move {
+    pub fn start_in_thread(mut self) -> JoinHandle<Result<(), String>> {
+        tokio::spawn(async move {
             self.start().await
-        });
-        handle
+        })
     }
 
-    pub async fn start(&mut self) -> () {
-        //let mut terminal: DefaultTerminal = ratatui::init();
+    /// Shows the events until the user quits, the process is asked to end or, when
+    /// set to, the producers are gone. The terminal is taken over here and given back
+    /// on every way out of this function, including a panic or the task being aborted.
+    /// Producers learn the viewer ended from their shutdown handle and from sends failing.
+    pub async fn start(&mut self) -> Result<(), String> {
+        let result = self.run().await;
+        self.shutdown.trigger();
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(true);
+        }
+        // blocked senders fail instead of waiting for room that never comes
+        self.event_receiver = None;
+        result
+    }
 
-        println!("EventLine started");
-        self.ui_handler.render(self);
+    async fn run(&mut self) -> Result<(), String> {
+        let mut signals = Signals::new()?;
+        let mut terminal = TerminalGuard::new()?;
+        let mut input = EventStream::new();
+        // the header clock and rate move every second, highlights fade faster
+        let mut next_tick = Instant::now() + IDLE_TICK;
+        // cleared when every producer is gone, so a closed channel is not polled again
+        let mut receiving = self.event_receiver.is_some();
+        let frame_interval = Duration::from_secs(1) / self.max_fps;
+        let mut last_frame: Option<Instant> = None;
+        let mut dirty = true;
         loop {
-            // Check for keyboard events without causing errors
-            match ui::process_keypress() {
-                Ok(exit) => {
-                    if exit {
-                        ratatui::restore();
-                        if let Some(tx) = self.shutdown_tx.take() {
-                            tx.send(true).unwrap_or_else(|_| println!("Error sending shutdown signal"))
+            // changes arriving faster than the frame rate wait for the next frame
+            let frame_due = last_frame.map_or(Instant::now(), |at| at + frame_interval);
+            if dirty && frame_due <= Instant::now() {
+                let started = Instant::now();
+                self.update_view();
+                self.ui_handler.render(&mut terminal, self)?;
+                self.loop_stats.record_frame(started.elapsed());
+                last_frame = Some(started);
+                dirty = false;
+            }
+            tokio::select! {
+                input_event = input.next() => match input_event {
+                    Some(Ok(CEvent::Key(key))) => {
+                        if let Some(action) = ui::key_action(self.input_mode, key) {
+                            // actions work on the rows as they are now
+                            self.update_view();
+                            self.last_error = None;
+                            if self.handle_action(action) {
+                                return Ok(());
+                            }
+                            dirty = true;
                         }
-                        return;
-                        //return Ok(());
                     }
-                }
-                Err(_) => {}
-            }
-
-            // Check for any events received through the channel
-            match &mut self.event_receiver {
-                Some(receiver) => {
-                    tokio::select! {
-                        maybe_events = receiver.recv() => {
-                            match maybe_events {
-                                Some(events) => {
-                                    // Process the received event
-                                    //self.data_list = EventLine::sort_map_by_time(&events, ASC, events.last_update.clone()).0;
-                                    self.data_list = EventLine::sort_map_by_key(&events, events.last_update.clone()).0;
-                                    self.events_data = Some(*events);
-                                },
-                                None => {
-                                    // Channel is closed
-                                }
+                    Some(Ok(CEvent::Resize(_, _))) => dirty = true,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        self.last_error = Some(format!("error: {}", e));
+                        dirty = true;
+                    }
+                    // no keyboard anymore, nothing could end the viewer
+                    None => return Err(String::from("error: terminal input closed")),
+                },
+                message = next_message(&mut self.event_receiver), if receiving => {
+                    match message {
+                        Some(message) => self.apply_pending(message),
+                        // every producer is gone, what they sent stays on screen
+                        None => {
+                            receiving = false;
+                            self.disconnected = true;
+                            if self.exit_on_disconnect {
+                                return Ok(());
                             }
                         }
-                        // You can add other async operations here to select between them
-                        // For example:
-                        _ = tokio::time::sleep(Duration::from_millis(10)) => {
-                            // Timeout occurred
-                        }
                     }
+                    dirty = true;
                 }
-                None => {} // Handle the case when event_receiver is None
+                _ = tokio::time::sleep_until(frame_due), if dirty => {}
+                _ = tokio::time::sleep_until(next_tick) => {
+                    self.expire_changes();
+                    // events cross a relative time without any update, the filter runs again
+                    if self.filter.as_ref().is_some_and(Filter::has_relative) {
+                        self.view_stale = true;
+                    }
+                    let tick = if self.changes.is_empty() { IDLE_TICK } else { FADE_TICK };
+                    next_tick = Instant::now() + tick;
+                    dirty = true;
+                }
+                _ = signals.recv() => return Ok(()),
+            }
+            // a change starts fading at once, not at the next idle tick
+            if !self.changes.is_empty() {
+                next_tick = next_tick.min(Instant::now() + FADE_TICK);
             }
+        }
+    }
 
-            //terminal.draw(|f| ui::render(f, self));
-            //self.term.borrow_mut().draw(|f| ui::render(f, self));
-            self.ui_handler.render(self)
+    /// Applies message right away, without going through the channel, and updates
+    /// the listed rows like before a frame. For embedding and measuring the viewer.
+    pub fn apply(&mut self, message: EventMessage) {
+        self.apply_message(message);
+        self.update_view();
+    }
+
+    /// Applies message and the messages queued behind it, leaving the listed rows
+    /// to be updated once before the next frame.
+    fn apply_pending(&mut self, message: EventMessage) {
+        let started = Instant::now();
+        self.apply_message(message);
+        let mut applied = 1;
+        while started.elapsed() < APPLY_BUDGET
+            && let Some(message) = self.event_receiver.as_mut().and_then(|receiver| receiver.try_recv())
+        {
+            self.apply_message(message);
+            applied += 1;
         }
+        self.loop_stats.record_applied(applied);
     }
 
-    fn apply_style(d: &Events<dyn Event + Send>) -> Vec<String> {
-        let mut v: Vec<String> = Vec::new();
-        for (_, event) in d.events_map.iter() {
-            v.push(event.get_event_presentation());
+    /// Applies a producer message to the events, marking the changed ones
+    /// to be listed again.
+    fn apply_message(&mut self, message: EventMessage) {
+        match message {
+            EventMessage::Upsert(key, event) => self.upsert_event(key, event),
+            EventMessage::Batch(events) => {
+                for (key, event) in events {
+                    self.upsert_event(key, event);
+                }
+            }
+            EventMessage::Remove(key) => {
+                self.stale_keys.insert(key.clone());
+                // the row stays listed, struck through, until the change expires
+                match self.events_data.as_mut().and_then(|events| events.events_map.remove(&key)) {
+                    Some(event) => {
+                        self.severity_counts[event.get_event_severity().index()] -= 1;
+                        self.facets.remove(event.as_ref());
+                        self.changes.record_removed(key, event);
+                    }
+                    None => {
+                        self.index.remove(&key);
+                        self.rows.invalidate(&key);
+                    }
+                }
+            }
+            EventMessage::Clear => {
+                if let Some(events) = self.events_data.as_mut() {
+                    for (key, event) in events.events_map.drain() {
+                        self.changes.record_removed(key, event);
+                    }
+                }
+                self.severity_counts = [0; Severity::ALL.len()];
+                self.facets.clear();
+                self.view_stale = true;
+            }
+            EventMessage::Snapshot(events) => self.apply_snapshot(*events),
+            EventMessage::Status(source, status) => match self.sources.iter_mut().find(|(name, _)| *name == source) {
+                Some((_, current)) => *current = status,
+                None => self.sources.push((source, status)),
+            },
         }
-        v
     }
 
-    /// Sorts a HashMap by its string keys in ascending order and returns a vector of event presentations.
-    /// Also returns the index of the event with the key that matches last_update.
-    fn sort_map_by_key(data: &Events<dyn Event + Send>, last_update: String) -> (Vec<String>, usize)
-    {
-        let mut last_index_update = 0;
+    /// Stores one event, counting it as the latest update.
+    fn upsert_event(&mut self, key: String, event: Box<dyn Event + Send>) {
+        self.columns.discover(&event.get_event_fields());
+        self.index.upsert(&key, event.as_ref());
+        let events = self.events_data.get_or_insert_with(Events::default);
+        match events.events_map.get(&key) {
+            Some(old) if changes::same_event(old.as_ref(), event.as_ref()) => {}
+            Some(_) => {
+                self.changes.record(&key, ChangeKind::Modified);
+                self.rows.invalidate(&key);
+            }
+            None => {
+                self.changes.record(&key, ChangeKind::Added);
+                self.rows.invalidate(&key);
+            }
+        }
+        if let Some(old) = events.events_map.get(&key) {
+            self.severity_counts[old.get_event_severity().index()] -= 1;
+            self.facets.remove(old.as_ref());
+        }
+        self.severity_counts[event.get_event_severity().index()] += 1;
+        self.facets.add(event.as_ref());
+        self.rate.record(1);
+        events.global_counter += 1;
+        events.last_update = key.clone();
+        self.stale_keys.insert(key.clone());
+        events.events_map.insert(key, event);
+    }
 
-        // Get all keys from the map
-        let mut keys: Vec<String> = data.events_map.keys().cloned().collect();
+    /// Replaces the displayed events with a new snapshot, recording what
+    /// changed since the previous one.
+    fn apply_snapshot(&mut self, events: Events<dyn Event + Send>) {
+        let mut previous = self.events_data.take().map(|e| e.events_map).unwrap_or_default();
+        self.severity_counts = [0; Severity::ALL.len()];
+        let mut updated = 0;
+        for (key, event) in &events.events_map {
+            self.columns.discover(&event.get_event_fields());
+            self.severity_counts[event.get_event_severity().index()] += 1;
+            match previous.remove(key) {
+                Some(old) if changes::same_event(old.as_ref(), event.as_ref()) => continue,
+                Some(old) => {
+                    self.changes.record(key, ChangeKind::Modified);
+                    self.facets.remove(old.as_ref());
+                }
+                None => self.changes.record(key, ChangeKind::Added),
+            }
+            self.facets.add(event.as_ref());
+            self.rows.invalidate(key);
+            self.stale_keys.insert(key.clone());
+            updated += 1;
+        }
+        self.rate.record(updated);
+        // what is left of the previous snapshot was removed
+        for (key, event) in previous {
+            self.stale_keys.insert(key.clone());
+            self.facets.remove(event.as_ref());
+            self.changes.record_removed(key, event);
+        }
+        self.index.sync(&events);
+        self.index_removed();
+        self.events_data = Some(events);
+    }
 
-        // Sort the keys
-        keys.sort();
+    /// Puts the removed events still displayed back in the index after a sync.
+    fn index_removed(&mut self) {
+        for (key, event) in self.changes.removed_events() {
+            self.index.upsert(key, event.as_ref());
+        }
+    }
 
-        // Build the ordered result
-        let mut result = Vec::with_capacity(data.events_map.len());
+    /// Drops the expired changes, unlisting the removed events whose time is up.
+    fn expire_changes(&mut self) {
+        let gone = self.changes.expire();
+        if gone.is_empty() {
+            return;
+        }
+        for key in gone {
+            self.index.remove(&key);
+            self.rows.invalidate(&key);
+            self.stale_keys.insert(key);
+        }
+    }
 
-        for (i, key) in keys.iter().enumerate() {
-            if let Some(event) = data.events_map.get(key) {
-                result.push(event.get_event_presentation());
-                if last_update == *key {
-                    last_index_update = i;
-                }
+    /// Brings the listed rows up to date before a frame: the changed events are
+    /// listed again one at a time, or every event when refresh_view is due.
+    fn update_view(&mut self) {
+        // many changes, like a first snapshot, are listed faster from the index
+        if self.view_stale || self.stale_keys.len() > self.index.len() / 8 {
+            self.refresh_view();
+            return;
+        }
+        if self.stale_keys.is_empty() {
+            return;
+        }
+        for key in std::mem::take(&mut self.stale_keys) {
+            self.unlist(&key);
+            if let Some(row) = self.filtered_row(&key) {
+                self.list(key, row);
+            }
+        }
+        self.finish_view();
+    }
+
+    /// Lists every event again from the index, leaving out the events below the severity
+    /// threshold or rejected by the filter and, when filtering, those not matching the search.
+    /// Needed when one of them or the sort changed.
+    fn refresh_view(&mut self) {
+        self.view_stale = false;
+        self.stale_keys.clear();
+        if self.events_data.is_none() {
+            return;
+        }
+        let rows: Vec<(String, FilteredRow)> = self
+            .index
+            .keys()
+            .filter_map(|key| Some((key.to_string(), self.filtered_row(key)?)))
+            .collect();
+        self.filtered_rows.clear();
+        self.listed_times.clear();
+        self.filtered_count = 0;
+        self.match_count = 0;
+        // in index order already
+        let mut listed = Vec::new();
+        for (key, row) in rows {
+            self.count(&row, 1);
+            if row.listed {
+                listed.push(row.position.clone());
             }
+            self.filtered_rows.insert(key, row);
         }
+        self.data_keys = ListedKeys::from_sorted(listed);
+        self.finish_view();
+    }
+
+    /// How the event under key is listed, None when it is below the threshold,
+    /// rejected by the filter or gone.
+    fn filtered_row(&self, key: &str) -> Option<FilteredRow> {
+        let position = self.index.position(key)?;
+        let event = self.event(key)?;
+        if event.get_event_severity() < self.min_severity
+            || self.filter.as_ref().is_some_and(|filter| !filter.matches(event))
+        {
+            return None;
+        }
+        // only a search needs the text of the event
+        let matched = self
+            .search
+            .as_ref()
+            .is_some_and(|search| search.is_match(&self.search_text(&self.rows.get(key, event))));
+        Some(FilteredRow {
+            position: position.clone(),
+            time: event.get_event_time().timestamp_millis(),
+            matched,
+            listed: matched || self.search.is_none() || !self.filter_matches,
+        })
+    }
+
+    fn list(&mut self, key: String, row: FilteredRow) {
+        self.count(&row, 1);
+        if row.listed {
+            self.data_keys.insert(row.position.clone());
+        }
+        self.filtered_rows.insert(key, row);
+    }
 
-        (result, last_index_update)
+    /// Takes the event under key out of the listed rows, as it was listed.
+    fn unlist(&mut self, key: &str) {
+        if let Some(row) = self.filtered_rows.remove(key) {
+            self.count(&row, -1);
+            if row.listed {
+                self.data_keys.remove(&row.position);
+            }
+        }
     }
 
+    /// Adds a filtered row to the counters and the listed times, or takes it out.
+    fn count(&mut self, row: &FilteredRow, delta: isize) {
+        self.filtered_count = self.filtered_count.saturating_add_signed(delta);
+        if row.matched {
+            self.match_count = self.match_count.saturating_add_signed(delta);
+        }
+        if row.listed {
+            let count = self.listed_times.entry(row.time).or_default();
+            *count = count.saturating_add_signed(delta);
+            if *count == 0 {
+                self.listed_times.remove(&row.time);
+            }
+        }
+    }
 
-    /// Sorts a HashMap of events by timestamp in ascending or descending order.
-    /// Returns a vector of event presentations in the sorted order and the index of the event with the specified ID.
-    pub fn sort_map_by_time(
-        data: &Events<dyn Event + Send>,
-        order: i32,
-        last_update: String
-    ) -> (Vec<String>, usize) {
-        let count = data.events_map.len();
-        // we know the capacity we want to re-organize the way the element are ordered
-        let mut result = vec![String::new(); count];
-        let mut timestamps = vec![0; count];
-        let mut ids = vec![String::new(); count];
+    /// Updates what depends on the listed rows once they changed.
+    fn finish_view(&mut self) {
+        self.facets.update_top();
+        self.listed_range = self
+            .listed_times
+            .first_key_value()
+            .zip(self.listed_times.last_key_value())
+            .map(|((start, _), (end, _))| (*start, *end));
+        self.last_update_index = self
+            .events_data
+            .as_ref()
+            .and_then(|events| self.listed_index(&events.last_update));
+        self.restore_selection();
+        if self.follow {
+            self.follow_last_update();
+        }
+    }
 
-        let mut index = 0;
+    /// Row of the event under key, if it is listed.
+    fn listed_index(&self, key: &str) -> Option<usize> {
+        let row = self.filtered_rows.get(key).filter(|row| row.listed)?;
+        self.data_keys.index_of(&row.position)
+    }
 
-        for (key, event) in &data.events_map {
-            let mut pos_to_insert = index;
-            let v = event.get_event_time().timestamp_millis();
+    /// Selects the row of the most recently updated event.
+    fn follow_last_update(&mut self) {
+        if let Some(index) = self.last_update_index {
+            self.select_index(index);
+        }
+    }
 
-            let _debug_time = event.get_event_time();
-            let _debug = event.get_event_presentation();
+    /// Changes the sort order with change and re-indexes the current snapshot.
+    fn update_sort(&mut self, change: impl FnOnce(&mut SortSpec)) {
+        let mut spec = self.index.spec().clone();
+        change(&mut spec);
+        self.index.set_spec(spec, self.events_data.as_ref());
+        self.index_removed();
+        self.refresh_view();
+    }
 
-            // Find position to insert based on the timestamp ordering
-            for i in 0..index {
-                if EventLine::compare_using(v, timestamps[i], order) {
-                    // If condition is true, we shift elements down and insert at position i
-                    EventLine::shift_down_from_index(&mut timestamps, &mut result, &mut ids, index, i);
-                    pos_to_insert = i;
-                    break;
+    /// Applies a user action to the view. Returns true when the viewer should exit.
+    fn handle_action(&mut self, action: ui::Action) -> bool {
+        let page = self.ui_handler.page_size().max(1) as isize;
+        match action {
+            ui::Action::Quit => return true,
+            // Esc closes the detail pane first, then clears the search and the filter, then quits
+            ui::Action::Back => {
+                if self.detail_open {
+                    self.detail_open = false;
+                } else if self.search.is_some() {
+                    self.search = None;
+                    self.refresh_view();
+                } else if self.filter.is_some() {
+                    self.filter = None;
+                    self.refresh_view();
+                } else {
+                    return true;
+                }
+            }
+            // moving by hand stops following the updates
+            ui::Action::Up => {
+                self.follow = false;
+                self.move_selection(-1);
+            }
+            ui::Action::Down => {
+                self.follow = false;
+                self.move_selection(1);
+            }
+            ui::Action::PageUp => {
+                self.follow = false;
+                self.move_selection(-page);
+            }
+            ui::Action::PageDown => {
+                self.follow = false;
+                self.move_selection(page);
+            }
+            ui::Action::Home => {
+                self.follow = false;
+                self.select_index(0);
+            }
+            ui::Action::End => {
+                self.follow = false;
+                self.select_index(usize::MAX);
+            }
+            ui::Action::ToggleFollow => {
+                self.follow = !self.follow;
+                if self.follow {
+                    self.follow_last_update();
+                }
+            }
+            ui::Action::RaiseSeverity => {
+                self.min_severity = self.min_severity.raise();
+                self.refresh_view();
+            }
+            ui::Action::LowerSeverity => {
+                self.min_severity = self.min_severity.lower();
+                self.refresh_view();
+            }
+            ui::Action::ToggleTimeline => self.timeline.toggle(),
+            ui::Action::ZoomIn | ui::Action::ZoomOut => {
+                if let Some(full) = self.time_range() {
+                    let factor = if action == ui::Action::ZoomIn { 0.5 } else { 2.0 };
+                    self.timeline.zoom(full, factor, self.selected_time());
+                }
+            }
+            ui::Action::PanLeft | ui::Action::PanRight => {
+                if let Some(full) = self.time_range() {
+                    let fraction = if action == ui::Action::PanLeft { -0.25 } else { 0.25 };
+                    self.timeline.pan(full, fraction);
+                }
+            }
+            ui::Action::FitTimeline => self.timeline.fit(),
+            // severity, then the field of the column under the cursor
+            ui::Action::CycleTimelineColor => {
+                let color = match (self.timeline.color(), self.columns.current()) {
+                    (TimelineColor::Severity, Some(name)) => TimelineColor::Field(name.to_string()),
+                    _ => TimelineColor::Severity,
+                };
+                self.timeline.set_color(color);
+            }
+            ui::Action::ToggleDetail => {
+                self.detail_open = !self.detail_open && self.selected.is_some();
+            }
+            ui::Action::ToggleView => {
+                self.view_mode = match self.view_mode {
+                    ViewMode::List => ViewMode::Table,
+                    ViewMode::Table => ViewMode::List,
+                };
+                // the search looks at what is displayed
+                self.refresh_view();
+            }
+            ui::Action::ColumnLeft | ui::Action::ColumnRight => {
+                self.columns.move_cursor(if action == ui::Action::ColumnLeft { -1 } else { 1 });
+                // the facet panel shows the field of the current column
+                if self.facets.field().is_some() {
+                    self.open_facets();
+                }
+            }
+            ui::Action::OpenFacets => {
+                self.open_facets();
+                self.input_mode = ui::InputMode::Facets;
+            }
+            ui::Action::CloseFacets => {
+                self.facets.close();
+                self.input_mode = ui::InputMode::Normal;
+            }
+            ui::Action::FacetUp => self.facets.move_cursor(-1),
+            ui::Action::FacetDown => self.facets.move_cursor(1),
+            ui::Action::ApplyFacet => self.apply_facet(),
+            ui::Action::MoveColumnLeft => self.columns.shift_current(-1),
+            ui::Action::MoveColumnRight => self.columns.shift_current(1),
+            ui::Action::HideColumn => {
+                self.columns.hide_current();
+                self.refresh_view();
+            }
+            ui::Action::ShowColumns => {
+                self.columns.show_all();
+                self.refresh_view();
+            }
+            ui::Action::StartSearch => {
+                self.saved_search = self.search.clone();
+                self.prompt = self.search.as_ref().map(|s| s.query().to_string()).unwrap_or_default();
+                self.input_mode = ui::InputMode::Search;
+            }
+            ui::Action::StartCommand => {
+                self.saved_filter = self.filter.clone();
+                self.prompt = match self.filter.as_ref() {
+                    Some(filter) => format!("filter {}", filter),
+                    None => String::from("filter "),
+                };
+                self.input_mode = ui::InputMode::Command;
+            }
+            ui::Action::Input(c) => {
+                self.prompt.push(c);
+                self.update_prompt();
+            }
+            ui::Action::DeleteChar => {
+                self.prompt.pop();
+                self.update_prompt();
+            }
+            ui::Action::CycleSearchMode => {
+                if self.input_mode == ui::InputMode::Search {
+                    self.search_mode = self.search_mode.next();
+                    self.update_search();
+                }
+            }
+            ui::Action::Submit => {
+                if self.input_mode == ui::InputMode::Command {
+                    // a command that does not run keeps the prompt open with its error
+                    if let Err(e) = self.run_command() {
+                        self.prompt_error = Some(e);
+                        return false;
+                    }
+                }
+                self.input_mode = ui::InputMode::Normal;
+                self.prompt_error = None;
+            }
+            ui::Action::Cancel => {
+                match self.input_mode {
+                    ui::InputMode::Search => self.search = self.saved_search.take(),
+                    ui::InputMode::Command => self.filter = self.saved_filter.take(),
+                    ui::InputMode::Normal | ui::InputMode::Facets => {}
+                }
+                self.input_mode = ui::InputMode::Normal;
+                self.prompt_error = None;
+                self.refresh_view();
+            }
+            ui::Action::ToggleFilter => {
+                self.filter_matches = !self.filter_matches;
+                self.refresh_view();
+            }
+            ui::Action::NextMatch => {
+                self.follow = false;
+                self.jump_to_match(true);
+            }
+            ui::Action::PrevMatch => {
+                self.follow = false;
+                self.jump_to_match(false);
+            }
+            ui::Action::CycleSort => self.update_sort(SortSpec::cycle_preset),
+            ui::Action::SortByColumn => {
+                if let Some(name) = self.columns.current() {
+                    let key = SortKey::Field(name.to_string());
+                    self.update_sort(|spec| spec.sort_by(key));
+                }
+            }
+            ui::Action::ThenByColumn => {
+                if let Some(name) = self.columns.current() {
+                    let key = SortKey::Field(name.to_string());
+                    self.update_sort(|spec| spec.then_by(key));
                 }
             }
+        }
+        false
+    }
 
-            timestamps[pos_to_insert] = v;
-            result[pos_to_insert] = event.get_event_presentation();
-            ids[pos_to_insert] = key.clone();
-            index += 1;
+    /// Applies what is typed in the prompt as it changes.
+    fn update_prompt(&mut self) {
+        match self.input_mode {
+            ui::InputMode::Search => self.update_search(),
+            ui::InputMode::Command => self.preview_command(),
+            ui::InputMode::Normal | ui::InputMode::Facets => {}
         }
+    }
 
-        // Find the index of the last_update event
-        for (i, id) in ids.iter().enumerate() {
-            if id == &last_update {
-                return (result, i);
+    /// Applies a :filter expression live while it is typed. Until it parses,
+    /// the previous filter stays and the parse error is shown next to the prompt.
+    fn preview_command(&mut self) {
+        self.prompt_error = None;
+        let Some(("filter", expr)) = self.prompt.trim_start().split_once(' ') else {
+            return;
+        };
+        if expr.trim().is_empty() {
+            self.filter = None;
+        } else {
+            match Filter::parse(expr) {
+                Ok(filter) => self.filter = Some(filter),
+                Err(e) => self.prompt_error = Some(e),
             }
         }
+        self.refresh_view();
+    }
+
+    /// Runs the command typed after ':'.
+    fn run_command(&mut self) -> Result<(), String> {
+        let prompt = self.prompt.trim();
+        let (command, argument) = prompt.split_once(' ').unwrap_or((prompt, ""));
+        match command {
+            "filter" => {
+                self.filter = match argument.trim() {
+                    "" => None,
+                    expr => Some(Filter::parse(expr)?),
+                };
+                self.refresh_view();
+                Ok(())
+            }
+            "" => Ok(()),
+            _ => Err(format!("unknown command '{}', try :filter <expression>", command)),
+        }
+    }
+
+    /// Applies the query typed in the prompt as it changes. An invalid regex
+    /// keeps the previous search and is reported under the list.
+    fn update_search(&mut self) {
+        if self.prompt.is_empty() {
+            self.search = None;
+            self.prompt_error = None;
+        } else {
+            match Search::new(&self.prompt, self.search_mode) {
+                Ok(search) => {
+                    self.search = Some(search);
+                    self.prompt_error = None;
+                }
+                Err(e) => self.prompt_error = Some(e),
+            }
+        }
+        self.refresh_view();
+    }
+
+    /// Text the search is matched against: what the row shows, so the
+    /// presentation in list mode and the visible cells in table mode.
+    fn search_text(&self, row: &RowText) -> String {
+        match self.view_mode {
+            ViewMode::List => row.presentation.clone(),
+            ViewMode::Table => {
+                self.columns
+                    .visible()
+                    .filter_map(|column| row.fields.iter().find(|(name, _)| *name == column.name))
+                    .map(|(_, value)| value.as_str())
+                    // a match cannot span two cells
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+
+    /// Selects the next (or previous) row matching the search, wrapping around.
+    fn jump_to_match(&mut self, forward: bool) {
+        let Some(search) = self.search.as_ref() else {
+            return;
+        };
+        let len = self.data_keys.len();
+        let start = self.selected.unwrap_or(if forward { len.saturating_sub(1) } else { 0 });
+        let is_match = |(_, key): &(usize, &str)| {
+            self.row(key)
+                .is_some_and(|row| search.is_match(&self.search_text(&row)))
+        };
+        // the rows after the selection then from the top, or before it then from the bottom
+        let found = if forward {
+            let rows = || self.data_keys.iter().enumerate();
+            rows().skip(start + 1).chain(rows().take(start + 1)).find(is_match)
+        } else {
+            let rows = || (0..len).rev().zip(self.data_keys.iter().rev());
+            rows().skip(len - start).chain(rows().take(len - start)).find(is_match)
+        };
+        if let Some((index, _)) = found {
+            self.select_index(index);
+        }
+    }
 
-        (result, 0)
+    /// Moves the selection by delta rows, starting from the top when nothing is selected.
+    fn move_selection(&mut self, delta: isize) {
+        let target = self.selected.map_or(0, |i| i.saturating_add_signed(delta));
+        self.select_index(target);
     }
 
-    /// Shifts elements in three vectors down from start index to stop index.
-    fn shift_down_from_index(
-        timestamps: &mut Vec<i64>,
-        presentations: &mut Vec<String>,
-        ids: &mut Vec<String>,
-        start: usize,
-        stop: usize
-    ) {
-        // No need to resize the vector there are declared as a fixed size slice we know
-        // exactly the capacity we need.
+    /// Selects the row at index, clamped to the list bounds, and remembers its key.
+    fn select_index(&mut self, index: usize) {
+        if self.data_keys.is_empty() {
+            self.selected = None;
+            self.selected_key = None;
+            return;
+        }
+        let index = index.min(self.data_keys.len() - 1);
+        self.selected = Some(index);
+        self.selected_key = self.data_keys.get(index).map(str::to_string);
+        // the timeline cursor follows the selection
+        if let Some(time) = self.selected_time() {
+            self.timeline.keep_in_view(time);
+        }
+    }
 
-        // Shift elements down
-        for i in (stop + 1..=start).rev() {
-            timestamps[i] = timestamps[i - 1];
-            presentations[i] = presentations[i - 1].clone();
-            ids[i] = ids[i - 1].clone();
+    /// Counts the values of the field of the current column in the facet panel.
+    fn open_facets(&mut self) {
+        match self.columns.current().map(str::to_string) {
+            Some(field) => self.facets.open(&field, self.events_data.as_ref()),
+            None => self.facets.close(),
         }
     }
 
-    /// Compares two i64 values based on the specified order.
-    fn compare_using(a: i64, b: i64, order: i32) -> bool {
-        match order {
-            1 => a >= b,
-            0 => a < b,
-            _ => false,
+    /// Narrows the filter to the events having the value under the facet cursor,
+    /// then closes the panel.
+    fn apply_facet(&mut self) {
+        let (Some(field), Some(value)) = (self.facets.field(), self.facets.selected()) else {
+            return;
+        };
+        let condition = Filter::equals(field, value);
+        self.filter = Some(match self.filter.take() {
+            Some(filter) => filter.and(condition),
+            None => condition,
+        });
+        self.facets.close();
+        self.input_mode = ui::InputMode::Normal;
+        self.refresh_view();
+    }
+
+    /// Seconds since the epoch of the selected event.
+    pub fn selected_time(&self) -> Option<f64> {
+        self.selected_event()
+            .map(|event| event.get_event_time().timestamp_millis() as f64 / 1000.0)
+    }
+
+    /// Re-locates the selected key after the listed rows changed.
+    /// If the selected event is gone, the selection stays at the same position.
+    fn restore_selection(&mut self) {
+        let Some(key) = self.selected_key.as_ref() else {
+            return;
+        };
+        match self.listed_index(key) {
+            Some(index) => self.selected = Some(index),
+            None => self.select_index(self.selected.unwrap_or(0)),
         }
     }
 }
+
+/// How an event passing the severity threshold and the filter is listed.
+struct FilteredRow {
+    // where it is in the index, and in data_keys when listed
+    position: SortPosition,
+    // Unix milliseconds
+    time: i64,
+    matched: bool,
+    // false when filtering on a search it does not match
+    listed: bool,
+}
+
+/// Next producer message, never ready without a channel. None once every sender is gone.
+async fn next_message(receiver: &mut Option<EventReceiver>) -> Option<EventMessage> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::search::SearchMode;
+    use crate::eventline::source::json::{JsonMapping, line_event};
+
+    fn event(id: usize, n: u64) -> Box<dyn Event + Send> {
+        let level = ["debug", "info", "warn", "error"][n as usize % 4];
+        let json = format!(r#"{{"id": "e{}", "n": {}, "level": "{}", "time": {}}}"#, id, n, level, 1_700_000_000 + n);
+        line_event("test", 0, json, &JsonMapping::default())
+    }
+
+    /// What the viewer lists, to compare the listed rows updated one event at a
+    /// time with the same rows listed from scratch.
+    #[derive(Debug, PartialEq)]
+    struct View {
+        keys: Vec<String>,
+        filtered_count: usize,
+        match_count: usize,
+        last_update_index: Option<usize>,
+        times: Vec<(i64, usize)>,
+    }
+
+    fn view(viewer: &EventLine) -> View {
+        View {
+            keys: viewer.data_keys.iter().map(str::to_string).collect(),
+            filtered_count: viewer.filtered_count,
+            match_count: viewer.match_count,
+            last_update_index: viewer.last_update_index,
+            times: viewer.listed_times.iter().map(|(time, count)| (*time, *count)).collect(),
+        }
+    }
+
+    #[test]
+    fn updating_the_view_lists_like_a_refresh() {
+        let mut viewer = EventLine::new(String::from("test")).unwrap();
+        viewer.min_severity = Severity::Info;
+        viewer.filter = Some(Filter::parse("n < 900").unwrap());
+        viewer.search = Some(Search::new("7", SearchMode::IgnoreCase).unwrap());
+        viewer.update_sort(SortSpec::cycle_preset);
+        let mut events = Events::default();
+        for id in 0..200 {
+            events.events_map.insert(format!("e{}", id), event(id, id as u64 * 5));
+        }
+        viewer.apply(EventMessage::Snapshot(Box::new(events)));
+
+        // a fixed sequence of updates landing all over the list
+        let mut seed = 12345u64;
+        for step in 0..500 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let id = (seed >> 33) as usize % 250;
+            let n = (seed >> 20) % 1000;
+            let message = match step % 5 {
+                0 => EventMessage::Remove(format!("e{}", id)),
+                1 => EventMessage::Batch(vec![(format!("e{}", id), event(id, n)), (format!("e{}", id + 1), event(id + 1, n))]),
+                _ => EventMessage::Upsert(format!("e{}", id), event(id, n)),
+            };
+            if step == 250 {
+                // as the ToggleFilter action does
+                viewer.filter_matches = false;
+                viewer.refresh_view();
+            }
+            viewer.apply(message);
+            let updated = view(&viewer);
+            viewer.refresh_view();
+            assert_eq!(updated, view(&viewer), "after step {}", step);
+        }
+        assert!(viewer.filtered_count > 0 && viewer.match_count > 0);
+    }
+
+    #[test]
+    fn snapshots_update_the_facet_counts() {
+        let mut viewer = EventLine::new(String::from("test")).unwrap();
+        let snapshot = |ids: std::ops::Range<usize>, shift: u64| {
+            let mut events = Events::default();
+            for id in ids {
+                events.events_map.insert(format!("e{}", id), event(id, id as u64 + shift));
+            }
+            EventMessage::Snapshot(Box::new(events))
+        };
+        viewer.apply(snapshot(0..20, 0));
+        viewer.facets.open("level", viewer.events_data.as_ref());
+        viewer.facets.move_cursor(2);
+        let selected = viewer.facets.selected().cloned();
+        viewer.apply(snapshot(5..30, 1));
+        viewer.apply(EventMessage::Upsert(String::from("e40"), event(40, 3)));
+
+        let mut recounted = Facets::default();
+        recounted.open("level", viewer.events_data.as_ref());
+        assert_eq!(viewer.facets.top(), recounted.top());
+        assert_eq!(viewer.facets.selected().cloned(), selected);
+    }
+}
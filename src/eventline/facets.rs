@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::eventline::{Event, Events};
+use super::field::FieldValue;
+
+/// Most frequent values kept for display.
+pub const TOP_K: usize = 50;
+
+/// Number of events having each value of one field, kept up to date as events
+/// are stored and removed.
+#[derive(Debug, Default)]
+pub struct Facets {
+    // None while the panel is closed, nothing is counted then
+    field: Option<String>,
+    counts: HashMap<Value, usize>,
+    // TOP_K most frequent values by decreasing count, recomputed when the counts changed
+    top: Vec<(FieldValue, usize)>,
+    changed: bool,
+    cursor: usize,
+}
+
+impl Facets {
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_deref()
+    }
+
+    /// Starts counting the values of field over events.
+    pub fn open(&mut self, field: &str, events: Option<&Events<dyn Event + Send>>) {
+        self.field = Some(field.to_string());
+        self.counts.clear();
+        self.cursor = 0;
+        if let Some(events) = events {
+            for event in events.events_map.values() {
+                self.add(event.as_ref());
+            }
+        }
+        self.changed = true;
+        self.update_top();
+    }
+
+    pub fn close(&mut self) {
+        *self = Facets::default();
+    }
+
+    /// Counts the value of a stored event.
+    pub fn add(&mut self, event: &(dyn Event + Send)) {
+        if let Some(value) = self.value_of(event) {
+            *self.counts.entry(value).or_default() += 1;
+            self.changed = true;
+        }
+    }
+
+    /// Stops counting the value of an event no longer stored.
+    pub fn remove(&mut self, event: &(dyn Event + Send)) {
+        if let Some(value) = self.value_of(event)
+            && let Some(count) = self.counts.get_mut(&value)
+        {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&value);
+            }
+            self.changed = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.changed = true;
+    }
+
+    /// Sorts the values again if the counts changed since the last call.
+    pub fn update_top(&mut self) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        let mut top: Vec<(FieldValue, usize)> =
+            self.counts.iter().map(|(value, count)| (value.0.clone(), *count)).collect();
+        let by_count =
+            |a: &(FieldValue, usize), b: &(FieldValue, usize)| b.1.cmp(&a.1).then_with(|| a.0.cmp_value(&b.0));
+        if top.len() > TOP_K {
+            top.select_nth_unstable_by(TOP_K - 1, by_count);
+            top.truncate(TOP_K);
+        }
+        top.sort_by(by_count);
+        // the cursor stays on its value as the counts change, if the value is still shown
+        let selected = self.selected().cloned();
+        self.top = top;
+        let kept = selected.and_then(|selected| self.top.iter().position(|(value, _)| same_value(value, &selected)));
+        self.cursor = match kept {
+            Some(index) => index,
+            None => self.cursor.min(self.top.len().saturating_sub(1)),
+        };
+    }
+
+    /// Most frequent values with their count.
+    pub fn top(&self) -> &[(FieldValue, usize)] {
+        &self.top
+    }
+
+    /// Number of distinct values.
+    pub fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.top.len().saturating_sub(1);
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+    }
+
+    /// Value under the cursor.
+    pub fn selected(&self) -> Option<&FieldValue> {
+        self.top.get(self.cursor).map(|(value, _)| value)
+    }
+
+    fn value_of(&self, event: &(dyn Event + Send)) -> Option<Value> {
+        let field = self.field.as_ref()?;
+        event.get_event_field(field).map(Value)
+    }
+}
+
+/// A counted value. Values are told apart as stored, not as displayed: two times
+/// in the same second or the number 1 and the text "1" are counted separately.
+#[derive(Debug)]
+struct Value(FieldValue);
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        same_value(&self.0, &other.0)
+    }
+}
+
+fn same_value(a: &FieldValue, b: &FieldValue) -> bool {
+    match (a, b) {
+        (FieldValue::Float(a), FieldValue::Float(b)) => a.to_bits() == b.to_bits(),
+        (a, b) => a == b,
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            FieldValue::Str(s) => s.hash(state),
+            FieldValue::Int(i) => i.hash(state),
+            FieldValue::Float(f) => f.to_bits().hash(state),
+            FieldValue::Bool(b) => b.hash(state),
+            FieldValue::Time(t) => t.hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::source::json::{JsonMapping, line_event};
+
+    fn event(city: &str) -> Box<dyn Event + Send> {
+        line_event("test", 0, format!(r#"{{"city": "{}"}}"#, city), &JsonMapping::default())
+    }
+
+    #[test]
+    fn counts_follow_the_events() {
+        let mut facets = Facets::default();
+        facets.open("city", None);
+        for city in ["Paris", "Lyon", "Paris", "Nice"] {
+            facets.add(event(city).as_ref());
+        }
+        facets.remove(event("Nice").as_ref());
+        facets.add(line_event("test", 0, String::from("{}"), &JsonMapping::default()).as_ref());
+        facets.update_top();
+        assert_eq!(facets.distinct(), 2);
+        assert_eq!(
+            facets.top(),
+            [(FieldValue::from("Paris"), 2), (FieldValue::from("Lyon"), 1)]
+        );
+    }
+
+    #[test]
+    fn cursor_stays_on_its_value() {
+        let mut facets = Facets::default();
+        facets.open("city", None);
+        for city in ["Paris", "Paris", "Lyon", "Nice"] {
+            facets.add(event(city).as_ref());
+        }
+        facets.update_top();
+        facets.move_cursor(1);
+        assert_eq!(facets.selected(), Some(&FieldValue::from("Lyon")));
+        // Lyon becomes the most frequent value
+        for _ in 0..2 {
+            facets.add(event("Lyon").as_ref());
+        }
+        facets.update_top();
+        assert_eq!((facets.cursor(), facets.selected()), (0, Some(&FieldValue::from("Lyon"))));
+        // once gone, the cursor stays at its row
+        for _ in 0..3 {
+            facets.remove(event("Lyon").as_ref());
+        }
+        facets.update_top();
+        assert_eq!(facets.cursor(), 0);
+        assert_eq!(facets.selected(), Some(&FieldValue::from("Paris")));
+    }
+}
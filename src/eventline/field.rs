@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+/// Typed value of an event field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Time(DateTime<Utc>),
+}
+
+/// A named field of an event. Events return their fields in display order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub value: FieldValue,
+}
+
+impl Field {
+    pub fn new(name: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        Field {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+impl FieldValue {
+    /// Total order used for sorting. Numbers compare across Int and Float,
+    /// values of different kinds are ordered by kind.
+    pub fn cmp_value(&self, other: &FieldValue) -> Ordering {
+        match (self, other) {
+            (FieldValue::Str(a), FieldValue::Str(b)) => a.cmp(b),
+            (FieldValue::Int(a), FieldValue::Int(b)) => a.cmp(b),
+            (FieldValue::Bool(a), FieldValue::Bool(b)) => a.cmp(b),
+            (FieldValue::Time(a), FieldValue::Time(b)) => a.cmp(b),
+            (a, b) => match (a.as_f64(), b.as_f64()) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                _ => a.rank().cmp(&b.rank()),
+            },
+        }
+    }
+
+    /// Numeric value of Int and Float fields.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Int(i) => Some(*i as f64),
+            FieldValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            FieldValue::Bool(_) => 0,
+            FieldValue::Int(_) | FieldValue::Float(_) => 1,
+            FieldValue::Time(_) => 2,
+            FieldValue::Str(_) => 3,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Str(s) => f.write_str(s),
+            FieldValue::Int(i) => write!(f, "{}", i),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Bool(b) => write!(f, "{}", b),
+            // same format as the rest of the viewer, in local time
+            FieldValue::Time(t) => write!(
+                f,
+                "{}",
+                t.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S%:z")
+            ),
+        }
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(v: String) -> Self {
+        FieldValue::Str(v)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(v: &str) -> Self {
+        FieldValue::Str(v.to_string())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(v: i64) -> Self {
+        FieldValue::Int(v)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(v: i32) -> Self {
+        FieldValue::Int(v as i64)
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(v: u64) -> Self {
+        FieldValue::Int(v as i64)
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(v: f64) -> Self {
+        FieldValue::Float(v)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(v: bool) -> Self {
+        FieldValue::Bool(v)
+    }
+}
+
+impl From<DateTime<Utc>> for FieldValue {
+    fn from(v: DateTime<Utc>) -> Self {
+        FieldValue::Time(v)
+    }
+}
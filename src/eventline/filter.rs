@@ -0,0 +1,681 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use regex::Regex;
+
+use super::eventline::Event;
+use super::field::FieldValue;
+
+/// A parsed filter expression, evaluated against the structured fields of
+/// each event.
+///
+/// ```text
+/// country == "France" && time > -5m
+/// city in ["Paris", "Lyon"] || !(message =~ "^debug")
+/// ```
+///
+/// - comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`
+/// - regex match: `=~`, `!~`
+/// - lists: `field in [a, b]`, `field not in [a, b]`
+/// - boolean operators: `&&`/`and`, `||`/`or`, `!`/`not`, parentheses
+/// - literals: `"text"`, numbers, `true`/`false`, relative times like `-5m`, `-2h`, `+1d`
+///   (units s, m, h, d, w, relative to now)
+///
+/// Names refer to event fields, a name that is not made of letters, digits, `_`
+/// and `.` or that is a keyword is quoted with backticks: `` `user-agent` == "curl" ``.
+/// `time` and `id` fall back to get_event_time()
+/// and get_event_id() for events without such a field. A string compared to a
+/// time or a number is converted first, so `time > "2024-01-01"` works.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = lex(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: source.len(),
+        };
+        let expr = parser.expr()?;
+        if let Some((token, at)) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected {} at {}", token, at + 1));
+        }
+        Ok(Filter {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Events whose field called name holds exactly value. Built from the value
+    /// itself, nothing is lost formatting and parsing it back.
+    pub fn equals(name: &str, value: &FieldValue) -> Self {
+        Filter {
+            source: format!("{} == {}", quote_name(name), literal(value)),
+            expr: Expr::Compare(Operand::Field(name.to_string()), CmpOp::Eq, Operand::Value(value.clone())),
+        }
+    }
+
+    /// Events matching both self and other.
+    pub fn and(self, other: Filter) -> Self {
+        Filter {
+            source: format!("({}) && {}", self.source, other.source),
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+
+    /// The expression as typed.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, event: &(dyn Event + Send)) -> bool {
+        self.expr.eval(event, Utc::now())
+    }
+
+    /// True when the filter compares to a time relative to now, so that events
+    /// stop or start matching as time passes.
+    pub fn has_relative(&self) -> bool {
+        self.expr.has_relative()
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Field(String),
+    Value(FieldValue),
+    // offset from the time the filter is evaluated
+    Relative(Duration),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CmpOp, Operand),
+    Match(Operand, Regex),
+    In(Operand, Vec<Operand>),
+    // a bare operand: true when present and not false
+    Truthy(Operand),
+}
+
+impl Expr {
+    fn eval(&self, event: &(dyn Event + Send), now: DateTime<Utc>) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(event, now) && b.eval(event, now),
+            Expr::Or(a, b) => a.eval(event, now) || b.eval(event, now),
+            Expr::Not(a) => !a.eval(event, now),
+            Expr::Compare(a, op, b) => {
+                let (Some(a), Some(b)) = (a.value(event, now), b.value(event, now)) else {
+                    return false;
+                };
+                match compare(&a, &b) {
+                    Some(ordering) => match op {
+                        CmpOp::Eq => ordering.is_eq(),
+                        CmpOp::Ne => ordering.is_ne(),
+                        CmpOp::Lt => ordering.is_lt(),
+                        CmpOp::Le => ordering.is_le(),
+                        CmpOp::Gt => ordering.is_gt(),
+                        CmpOp::Ge => ordering.is_ge(),
+                    },
+                    // values that cannot be ordered are only ever different
+                    None => *op == CmpOp::Ne,
+                }
+            }
+            Expr::Match(a, regex) => a
+                .value(event, now)
+                .is_some_and(|value| regex.is_match(&value.to_string())),
+            Expr::In(a, list) => {
+                let Some(a) = a.value(event, now) else {
+                    return false;
+                };
+                list.iter()
+                    .filter_map(|item| item.value(event, now))
+                    .any(|item| compare(&a, &item).is_some_and(Ordering::is_eq))
+            }
+            Expr::Truthy(a) => !matches!(a.value(event, now), None | Some(FieldValue::Bool(false))),
+        }
+    }
+
+    fn has_relative(&self) -> bool {
+        let relative = |operand: &Operand| matches!(operand, Operand::Relative(_));
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => a.has_relative() || b.has_relative(),
+            Expr::Not(a) => a.has_relative(),
+            Expr::Compare(a, _, b) => relative(a) || relative(b),
+            Expr::Match(a, _) | Expr::Truthy(a) => relative(a),
+            Expr::In(a, list) => relative(a) || list.iter().any(relative),
+        }
+    }
+}
+
+impl Operand {
+    fn value(&self, event: &(dyn Event + Send), now: DateTime<Utc>) -> Option<FieldValue> {
+        match self {
+            Operand::Field(name) => event.get_event_field(name).or_else(|| match name.as_str() {
+                "time" => Some(FieldValue::Time(event.get_event_time())),
+                "id" => Some(FieldValue::Str(event.get_event_id())),
+                "severity" => Some(FieldValue::Str(event.get_event_severity().to_string())),
+                _ => None,
+            }),
+            Operand::Value(value) => Some(value.clone()),
+            // None past the range of a time, nothing compares to it
+            Operand::Relative(offset) => now.checked_add_signed(*offset).map(FieldValue::Time),
+        }
+    }
+}
+
+/// Orders two values, converting a string to the type of the other value.
+fn compare(a: &FieldValue, b: &FieldValue) -> Option<Ordering> {
+    match (a, b) {
+        (FieldValue::Str(s), other) if !matches!(other, FieldValue::Str(_)) => {
+            Some(convert(s, other)?.cmp_value(other))
+        }
+        (other, FieldValue::Str(s)) if !matches!(other, FieldValue::Str(_)) => {
+            Some(other.cmp_value(&convert(s, other)?))
+        }
+        (FieldValue::Bool(_), FieldValue::Bool(_))
+        | (FieldValue::Time(_), FieldValue::Time(_))
+        | (FieldValue::Str(_), FieldValue::Str(_)) => Some(a.cmp_value(b)),
+        _ if a.as_f64().is_some() && b.as_f64().is_some() => Some(a.cmp_value(b)),
+        _ => None,
+    }
+}
+
+/// Parses s as a value of the same kind as like.
+fn convert(s: &str, like: &FieldValue) -> Option<FieldValue> {
+    match like {
+        FieldValue::Str(_) => Some(FieldValue::Str(s.to_string())),
+        FieldValue::Int(_) | FieldValue::Float(_) => s.trim().parse::<f64>().ok().map(FieldValue::Float),
+        FieldValue::Bool(_) => s.trim().parse::<bool>().ok().map(FieldValue::Bool),
+        FieldValue::Time(_) => parse_time(s.trim()).map(FieldValue::Time),
+    }
+}
+
+/// Last line of a regex error, the others draw the pattern for a multi-line display.
+pub fn regex_error(e: &regex::Error) -> String {
+    let message = e.to_string();
+    message.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string()
+}
+
+/// Parses an RFC 3339 time, or a date and time without offset taken as UTC.
+pub fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(time.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Relative(Duration),
+    Bool(bool),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Cmp(CmpOp),
+    Match,
+    NotMatch,
+    And,
+    Or,
+    Not,
+    In,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+            Token::Int(_) | Token::Float(_) => f.write_str("number"),
+            Token::Relative(_) => f.write_str("relative time"),
+            Token::Bool(b) => write!(f, "{}", b),
+            Token::LParen => f.write_str("'('"),
+            Token::RParen => f.write_str("')'"),
+            Token::LBracket => f.write_str("'['"),
+            Token::RBracket => f.write_str("']'"),
+            Token::Comma => f.write_str("','"),
+            Token::Cmp(_) | Token::Match | Token::NotMatch => f.write_str("operator"),
+            Token::And => f.write_str("'&&'"),
+            Token::Or => f.write_str("'||'"),
+            Token::Not => f.write_str("'!'"),
+            Token::In => f.write_str("'in'"),
+        }
+    }
+}
+
+/// Splits source into tokens along with their byte offset.
+fn lex(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<(usize, char)> = source.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (at, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+        let two = |a: char, b: char| c == a && next == Some(b);
+        let (token, len) = if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if two('=', '=') {
+            (Token::Cmp(CmpOp::Eq), 2)
+        } else if two('!', '=') {
+            (Token::Cmp(CmpOp::Ne), 2)
+        } else if two('<', '=') {
+            (Token::Cmp(CmpOp::Le), 2)
+        } else if two('>', '=') {
+            (Token::Cmp(CmpOp::Ge), 2)
+        } else if two('=', '~') {
+            (Token::Match, 2)
+        } else if two('!', '~') {
+            (Token::NotMatch, 2)
+        } else if two('&', '&') {
+            (Token::And, 2)
+        } else if two('|', '|') {
+            (Token::Or, 2)
+        } else if c == '<' {
+            (Token::Cmp(CmpOp::Lt), 1)
+        } else if c == '>' {
+            (Token::Cmp(CmpOp::Gt), 1)
+        } else if c == '!' {
+            (Token::Not, 1)
+        } else if c == '(' {
+            (Token::LParen, 1)
+        } else if c == ')' {
+            (Token::RParen, 1)
+        } else if c == '[' {
+            (Token::LBracket, 1)
+        } else if c == ']' {
+            (Token::RBracket, 1)
+        } else if c == ',' {
+            (Token::Comma, 1)
+        } else if c == '"' || c == '\'' {
+            let (value, len) = lex_quoted(&chars[i..]).map_err(|e| format!("{} at {}", e, at + 1))?;
+            (Token::Str(value), len)
+        } else if c == '`' {
+            let (name, len) = lex_quoted(&chars[i..]).map_err(|e| format!("{} at {}", e, at + 1))?;
+            if name.is_empty() {
+                return Err(format!("empty name at {}", at + 1));
+            }
+            (Token::Ident(name), len)
+        } else if c.is_ascii_digit() || ((c == '-' || c == '+') && next.is_some_and(|n| n.is_ascii_digit())) {
+            lex_number(&chars[i..]).map_err(|e| format!("{} at {}", e, at + 1))?
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = chars[i..].iter().take_while(|(_, c)| is_name_char(*c)).count();
+            let word: String = chars[i..i + len].iter().map(|(_, c)| c).collect();
+            let token = keyword(&word).unwrap_or(Token::Ident(word));
+            (token, len)
+        } else {
+            return Err(format!("unexpected '{}' at {}", c, at + 1));
+        };
+        tokens.push((token, at));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn keyword(word: &str) -> Option<Token> {
+    match word {
+        "and" => Some(Token::And),
+        "or" => Some(Token::Or),
+        "not" => Some(Token::Not),
+        "in" => Some(Token::In),
+        "true" => Some(Token::Bool(true)),
+        "false" => Some(Token::Bool(false)),
+        _ => None,
+    }
+}
+
+/// Field name as written in a filter, quoted with backticks when it would not
+/// be read back as that name.
+pub fn quote_name(name: &str) -> String {
+    let plain = name.chars().next().is_some_and(|c| (c.is_alphanumeric() || c == '_') && !c.is_ascii_digit())
+        && name.chars().all(is_name_char)
+        && keyword(name).is_none();
+    if plain {
+        return name.to_string();
+    }
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+/// Value as written in a filter. Times keep their sub-seconds and compare
+/// equal to the value once the text is parsed again.
+fn literal(value: &FieldValue) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    match value {
+        FieldValue::Str(s) => quote(s),
+        FieldValue::Int(i) => i.to_string(),
+        FieldValue::Float(v) if v.is_finite() => v.to_string(),
+        // NaN and inf, only read as text
+        FieldValue::Float(v) => quote(&v.to_string()),
+        FieldValue::Bool(b) => b.to_string(),
+        FieldValue::Time(t) => quote(&t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    }
+}
+
+/// Text between the quote at chars[0] and the next one, with \ escaping the next character.
+fn lex_quoted(chars: &[(usize, char)]) -> Result<(String, usize), String> {
+    let quote = chars[0].1;
+    let unterminated = if quote == '`' { "unterminated name" } else { "unterminated string" };
+    let mut value = String::new();
+    let mut i = 1;
+    while let Some((_, c)) = chars.get(i) {
+        match c {
+            '\\' => {
+                let (_, escaped) = chars.get(i + 1).ok_or(unterminated)?;
+                value.push(*escaped);
+                i += 2;
+            }
+            c if *c == quote => return Ok((value, i + 1)),
+            c => {
+                value.push(*c);
+                i += 1;
+            }
+        }
+    }
+    Err(String::from(unterminated))
+}
+
+/// Number, or relative time when a signed number is followed by a unit.
+fn lex_number(chars: &[(usize, char)]) -> Result<(Token, usize), String> {
+    let signed = matches!(chars[0].1, '-' | '+');
+    let len = chars
+        .iter()
+        .enumerate()
+        .take_while(|(i, (_, c))| c.is_ascii_digit() || *c == '.' || (*i == 0 && signed))
+        .count();
+    let text: String = chars[..len].iter().map(|(_, c)| c).collect();
+    let unit = chars.get(len).map(|(_, c)| *c).filter(|c| c.is_alphabetic());
+
+    let Some(unit) = unit else {
+        if let Ok(i) = text.parse::<i64>() {
+            return Ok((Token::Int(i), len));
+        }
+        return text
+            .parse::<f64>()
+            .map(|f| (Token::Float(f), len))
+            .map_err(|_| format!("invalid number '{}'", text));
+    };
+
+    if !signed {
+        return Err(format!("relative time needs a sign, like -{}{}", text, unit));
+    }
+    let amount: i64 = text.parse().map_err(|_| format!("invalid relative time '{}{}'", text, unit))?;
+    let offset = match unit {
+        's' => Duration::try_seconds(amount),
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => return Err(format!("unknown time unit '{}', use s, m, h, d or w", unit)),
+    };
+    let offset = offset.ok_or("relative time out of range")?;
+    Ok((Token::Relative(offset), len + 1))
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // length of the source, for errors at the end of input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    /// Error about the current token, or the end of input.
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some((token, at)) => format!("expected {}, found {} at {}", expected, token, at + 1),
+            None => format!("expected {} at {}", expected, self.end + 1),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.eat(&Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.expr()?;
+            if !self.eat(&Token::RParen) {
+                return Err(self.error("')'"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+        match self.peek() {
+            Some(Token::Cmp(op)) => {
+                let op = *op;
+                self.pos += 1;
+                Ok(Expr::Compare(left, op, self.operand()?))
+            }
+            Some(Token::Match) | Some(Token::NotMatch) => {
+                let negated = self.next() == Some(Token::NotMatch);
+                let at = self.tokens.get(self.pos).map_or(self.end, |(_, at)| *at);
+                let Some(Token::Str(pattern)) = self.next() else {
+                    self.pos -= 1;
+                    return Err(self.error("quoted regex"));
+                };
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| format!("invalid regex at {}: {}", at + 1, regex_error(&e)))?;
+                let expr = Expr::Match(left, regex);
+                Ok(if negated { Expr::Not(Box::new(expr)) } else { expr })
+            }
+            Some(Token::In) => {
+                self.pos += 1;
+                Ok(Expr::In(left, self.list()?))
+            }
+            Some(Token::Not) if self.tokens.get(self.pos + 1).map(|(t, _)| t) == Some(&Token::In) => {
+                self.pos += 2;
+                Ok(Expr::Not(Box::new(Expr::In(left, self.list()?))))
+            }
+            _ => Ok(Expr::Truthy(left)),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<Operand>, String> {
+        let close = match self.next() {
+            Some(Token::LBracket) => Token::RBracket,
+            Some(Token::LParen) => Token::RParen,
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("'[' or '('"));
+            }
+        };
+        let mut items = Vec::new();
+        if self.eat(&close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.operand()?);
+            if self.eat(&close) {
+                return Ok(items);
+            }
+            if !self.eat(&Token::Comma) {
+                return Err(self.error("',' or end of list"));
+            }
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        let operand = match self.peek() {
+            Some(Token::Ident(name)) => Operand::Field(name.clone()),
+            Some(Token::Str(s)) => Operand::Value(FieldValue::Str(s.clone())),
+            Some(Token::Int(i)) => Operand::Value(FieldValue::Int(*i)),
+            Some(Token::Float(f)) => Operand::Value(FieldValue::Float(*f)),
+            Some(Token::Bool(b)) => Operand::Value(FieldValue::Bool(*b)),
+            Some(Token::Relative(offset)) => Operand::Relative(*offset),
+            _ => return Err(self.error("field or value")),
+        };
+        self.pos += 1;
+        Ok(operand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::source::json::{JsonMapping, line_event};
+
+    fn event(json: &str) -> Box<dyn Event + Send> {
+        line_event("test", 0, json.to_string(), &JsonMapping::default())
+    }
+
+    fn matches(source: &str, json: &str) -> bool {
+        Filter::parse(source).unwrap().matches(event(json).as_ref())
+    }
+
+    #[test]
+    fn errors_give_their_position() {
+        let error = |source: &str| Filter::parse(source).unwrap_err();
+        assert_eq!(error("a == "), "expected field or value at 6");
+        assert_eq!(error("a == 1 )"), "unexpected ')' at 8");
+        assert_eq!(error("(a == 1"), "expected ')' at 8");
+        assert_eq!(error("a == 'x"), "unterminated string at 6");
+        assert_eq!(error("a # 1"), "unexpected '#' at 3");
+        assert_eq!(error("a =~ b"), "expected quoted regex, found 'b' at 6");
+        assert!(error("a =~ '('").starts_with("invalid regex at 6: "));
+        assert_eq!(error("`a"), "unterminated name at 1");
+        assert_eq!(error("`` == 1"), "empty name at 1");
+    }
+
+    #[test]
+    fn relative_times() {
+        let now = Utc::now();
+        let ago = (now - Duration::minutes(10)).to_rfc3339();
+        let json = format!(r#"{{"time": "{}"}}"#, ago);
+        assert!(matches("time < -5m", &json));
+        assert!(matches("time > -1h && time < +1d", &json));
+        assert!(!matches("time > -5m", &json));
+    }
+
+    #[test]
+    fn knows_when_it_has_a_relative_time() {
+        let relative = |source: &str| Filter::parse(source).unwrap().has_relative();
+        assert!(relative("time > -5m"));
+        assert!(relative("a == 1 || !(time in [-1h, 0])"));
+        assert!(!relative("time > '2024-01-01' && n < 5"));
+        assert!(!Filter::equals("n", &FieldValue::Int(1)).has_relative());
+    }
+
+    #[test]
+    fn relative_time_errors() {
+        let error = |source: &str| Filter::parse(source).unwrap_err();
+        assert_eq!(error("time > 5m"), "relative time needs a sign, like -5m at 8");
+        assert_eq!(error("time > -5y"), "unknown time unit 'y', use s, m, h, d or w at 8");
+        assert_eq!(error("time > -99999999999999w"), "relative time out of range at 8");
+    }
+
+    #[test]
+    fn relative_time_past_the_range_matches_nothing() {
+        // in range for a duration, not once added to now
+        let filter = Filter::parse("time < +9999999999d").unwrap();
+        assert!(!filter.matches(event("{}").as_ref()));
+    }
+
+    #[test]
+    fn quoted_names() {
+        let json = r#"{"user-agent": "curl", "and": 1}"#;
+        assert!(matches("`user-agent` == 'curl'", json));
+        assert!(matches("`and` == 1 and `user-agent` =~ '^cu'", json));
+        assert_eq!(quote_name("user.name"), "user.name");
+        assert_eq!(quote_name("user-agent"), "`user-agent`");
+        assert_eq!(quote_name("in"), "`in`");
+        assert_eq!(quote_name("1st"), "`1st`");
+        assert_eq!(quote_name("a`b"), "`a\\`b`");
+    }
+
+    #[test]
+    fn equals_reads_back_as_the_same_filter() {
+        let json = r#"{"@timestamp": "x", "quote": "it's \"here\"", "ratio": 0.5}"#;
+        let event = event(json);
+        for field in event.get_event_fields() {
+            let filter = Filter::equals(&field.name, &field.value);
+            assert!(filter.matches(event.as_ref()), "{}", filter);
+            assert!(Filter::parse(filter.source()).unwrap().matches(event.as_ref()), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn equals_keeps_sub_seconds() {
+        let time: DateTime<Utc> = "2024-05-01T10:00:00.250Z".parse().unwrap();
+        let filter = Filter::equals("time", &FieldValue::Time(time));
+        assert_eq!(filter.source(), r#"time == "2024-05-01T10:00:00.250Z""#);
+        assert!(matches(filter.source(), r#"{"time": "2024-05-01T10:00:00.250Z"}"#));
+        assert!(!matches(filter.source(), r#"{"time": "2024-05-01T10:00:00Z"}"#));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::eventline::{Event, Events};
+use super::sort::{SortPosition, SortSpec};
+
+/// Events keys kept in SortSpec order.
+///
+/// The index is updated one event at a time, so applying a snapshot only
+/// moves the events whose sort values changed, each in O(log n).
+#[derive(Debug, Default)]
+pub struct OrderIndex {
+    spec: SortSpec,
+    ordered: BTreeSet<SortPosition>,
+    // current position of every key, to find its entry in ordered
+    positions: HashMap<String, SortPosition>,
+}
+
+impl OrderIndex {
+    pub fn new(spec: SortSpec) -> Self {
+        OrderIndex {
+            spec,
+            ordered: BTreeSet::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn spec(&self) -> &SortSpec {
+        &self.spec
+    }
+
+    pub fn len(&self) -> usize {
+        self.ordered.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ordered.is_empty()
+    }
+
+    /// Inserts the event under key, or moves it if its sort values changed.
+    pub fn upsert(&mut self, key: &str, event: &(dyn Event + Send)) {
+        if let Some(current) = self.positions.get(key) {
+            if self.spec.is_at(current, event) {
+                return;
+            }
+            self.ordered.remove(current);
+        }
+        let position = self.spec.position_of(key, event);
+        self.ordered.insert(position.clone());
+        self.positions.insert(key.to_string(), position);
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if let Some(position) = self.positions.remove(key) {
+            self.ordered.remove(&position);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ordered.clear();
+        self.positions.clear();
+    }
+
+    /// Brings the index in line with a full snapshot: keys missing from it are
+    /// removed, the other events are upserted.
+    pub fn sync(&mut self, events: &Events<dyn Event + Send>) {
+        let gone: Vec<String> = self
+            .positions
+            .keys()
+            .filter(|key| !events.events_map.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in gone {
+            self.remove(&key);
+        }
+        for (key, event) in &events.events_map {
+            self.upsert(key, event.as_ref());
+        }
+    }
+
+    /// Switches to another order, re-indexing every event.
+    pub fn set_spec(&mut self, spec: SortSpec, events: Option<&Events<dyn Event + Send>>) {
+        self.spec = spec;
+        self.clear();
+        if let Some(events) = events {
+            self.sync(events);
+        }
+    }
+
+    /// Current position of the event under key.
+    pub fn position(&self, key: &str) -> Option<&SortPosition> {
+        self.positions.get(key)
+    }
+
+    /// Keys in sort order.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.ordered.iter().map(|position| position.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::sort::{SortKey, SortOrder};
+    use crate::eventline::source::json::{self, JsonMapping};
+
+    fn event(n: i64) -> Box<dyn Event + Send> {
+        json::line_event("test", 0, format!(r#"{{"n": {}}}"#, n), &JsonMapping::default())
+    }
+
+    fn events(values: &[(&str, i64)]) -> Events<dyn Event + Send> {
+        Events {
+            global_counter: values.len() as i32,
+            last_update: String::new(),
+            events_map: values.iter().map(|(key, n)| (key.to_string(), event(*n))).collect(),
+        }
+    }
+
+    fn by_n(order: SortOrder) -> OrderIndex {
+        OrderIndex::new(SortSpec::by(SortKey::Field(String::from("n")), order))
+    }
+
+    #[test]
+    fn upsert_moves_an_event() {
+        let mut index = by_n(SortOrder::Desc);
+        index.upsert("a", event(1).as_ref());
+        index.upsert("b", event(2).as_ref());
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["b", "a"]);
+        index.upsert("a", event(3).as_ref());
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(index.len(), 2);
+        index.remove("a");
+        index.remove("a");
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["b"]);
+        assert!(index.position("a").is_none());
+    }
+
+    #[test]
+    fn sync_drops_the_keys_that_are_gone() {
+        let mut index = by_n(SortOrder::Asc);
+        index.sync(&events(&[("a", 3), ("b", 1), ("c", 2)]));
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["b", "c", "a"]);
+        index.sync(&events(&[("c", 0), ("d", 5)]));
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["c", "d"]);
+        assert!(index.position("a").is_none() && index.position("b").is_none());
+        assert_eq!(index.position("c").map(SortPosition::key), Some("c"));
+    }
+
+    #[test]
+    fn set_spec_reorders_every_event() {
+        let events = events(&[("a", 3), ("b", 1), ("c", 2)]);
+        let mut index = by_n(SortOrder::Asc);
+        index.sync(&events);
+        index.set_spec(SortSpec::by(SortKey::Field(String::from("n")), SortOrder::Desc), Some(&events));
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["a", "c", "b"]);
+        index.set_spec(SortSpec::default(), None);
+        assert!(index.is_empty());
+    }
+}
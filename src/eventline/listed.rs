@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use super::sort::SortPosition;
+
+// most positions in a chunk, a fuller chunk is split in two
+const CHUNK: usize = 1024;
+
+/// Keys of the listed events in display order.
+///
+/// The positions are kept in sorted chunks of at most CHUNK entries, so listing or
+/// unlisting an event only shifts one chunk, and finding the row of an event only
+/// adds up the length of the chunks before it.
+#[derive(Debug, Default)]
+pub struct ListedKeys {
+    chunks: Vec<Vec<SortPosition>>,
+    len: usize,
+}
+
+impl ListedKeys {
+    /// Lists positions, which are already in order.
+    pub fn from_sorted(positions: Vec<SortPosition>) -> Self {
+        let len = positions.len();
+        let mut chunks = Vec::with_capacity(len.div_ceil(CHUNK));
+        let mut positions = positions.into_iter().peekable();
+        while positions.peek().is_some() {
+            chunks.push(positions.by_ref().take(CHUNK).collect());
+        }
+        ListedKeys { chunks, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Key listed at row index.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let (chunk, offset) = self.locate(index)?;
+        Some(self.chunks[chunk][offset].key())
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.chunks.iter().flatten().map(|position| position.key())
+    }
+
+    /// Keys of the rows in range, clamped to the list.
+    pub fn range(&self, rows: Range<usize>) -> impl Iterator<Item = &str> {
+        let (chunk, offset) = self.locate(rows.start).unwrap_or((self.chunks.len(), 0));
+        self.chunks[chunk..]
+            .iter()
+            .flatten()
+            .skip(offset)
+            .take(rows.len())
+            .map(|position| position.key())
+    }
+
+    /// Row of the event at position, if it is listed.
+    pub fn index_of(&self, position: &SortPosition) -> Option<usize> {
+        let chunk = self.chunk_of(position);
+        let offset = self.chunks.get(chunk)?.binary_search(position).ok()?;
+        Some(self.chunks[..chunk].iter().map(Vec::len).sum::<usize>() + offset)
+    }
+
+    pub fn insert(&mut self, position: SortPosition) {
+        if self.chunks.is_empty() {
+            self.chunks.push(Vec::new());
+        }
+        // past the last chunk, the position goes at the end of it
+        let chunk = self.chunk_of(&position).min(self.chunks.len() - 1);
+        let entries = &mut self.chunks[chunk];
+        let Err(offset) = entries.binary_search(&position) else {
+            return;
+        };
+        entries.insert(offset, position);
+        self.len += 1;
+        if entries.len() > CHUNK {
+            let half = entries.split_off(entries.len() / 2);
+            self.chunks.insert(chunk + 1, half);
+        }
+    }
+
+    /// Unlists the event at position, false when it was not listed.
+    pub fn remove(&mut self, position: &SortPosition) -> bool {
+        let chunk = self.chunk_of(position);
+        let Some(entries) = self.chunks.get_mut(chunk) else {
+            return false;
+        };
+        let Ok(offset) = entries.binary_search(position) else {
+            return false;
+        };
+        entries.remove(offset);
+        self.len -= 1;
+        if entries.is_empty() {
+            self.chunks.remove(chunk);
+        }
+        true
+    }
+
+    /// First chunk that can hold position, the number of chunks when every entry is before it.
+    fn chunk_of(&self, position: &SortPosition) -> usize {
+        self.chunks
+            .partition_point(|entries| entries.last().is_some_and(|last| last < position))
+    }
+
+    /// Chunk and offset in it of row index.
+    fn locate(&self, mut index: usize) -> Option<(usize, usize)> {
+        for (chunk, entries) in self.chunks.iter().enumerate() {
+            if index < entries.len() {
+                return Some((chunk, index));
+            }
+            index -= entries.len();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::sort::SortSpec;
+    use crate::eventline::source::LineEvent;
+
+    fn position(i: usize) -> SortPosition {
+        let key = format!("{:06}", i);
+        SortSpec::default().position_of(&key, &LineEvent::new("test", 0, key.clone()))
+    }
+
+    #[test]
+    fn stays_in_order_across_chunks() {
+        let mut listed = ListedKeys::default();
+        // odd rows first then even ones, every insert lands between two listed rows
+        for i in (1..3 * CHUNK).step_by(2).chain((0..3 * CHUNK).step_by(2)) {
+            listed.insert(position(i));
+        }
+        assert_eq!(listed.len(), 3 * CHUNK);
+        assert!(listed.chunks.len() > 3 && listed.chunks.iter().all(|chunk| chunk.len() <= CHUNK));
+        let keys: Vec<&str> = listed.iter().collect();
+        let expected: Vec<String> = (0..3 * CHUNK).map(|i| format!("{:06}", i)).collect();
+        assert_eq!(keys, expected);
+        for i in [0, 1, CHUNK, 2 * CHUNK + 7, 3 * CHUNK - 1] {
+            assert_eq!(listed.get(i), Some(expected[i].as_str()));
+            assert_eq!(listed.index_of(&position(i)), Some(i));
+        }
+        assert_eq!(listed.get(3 * CHUNK), None);
+        let range: Vec<&str> = listed.range(CHUNK - 2..CHUNK + 2).collect();
+        assert_eq!(range, expected[CHUNK - 2..CHUNK + 2]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut listed = ListedKeys::from_sorted((0..10).map(position).collect());
+        assert!(listed.remove(&position(3)));
+        assert!(!listed.remove(&position(3)));
+        assert!(!listed.remove(&position(42)));
+        assert_eq!(listed.len(), 9);
+        assert_eq!(listed.index_of(&position(3)), None);
+        assert_eq!(listed.index_of(&position(4)), Some(3));
+        for i in (0..10).filter(|i| *i != 3) {
+            listed.remove(&position(i));
+        }
+        assert!(listed.is_empty() && listed.chunks.is_empty());
+        assert_eq!(listed.range(0..5).count(), 0);
+    }
+
+    #[test]
+    fn insert_twice_lists_once() {
+        let mut listed = ListedKeys::default();
+        listed.insert(position(1));
+        listed.insert(position(1));
+        listed.insert(position(5));
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed.iter().collect::<Vec<_>>(), ["000001", "000005"]);
+    }
+}
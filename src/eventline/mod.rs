@@ -1,2 +1,19 @@
+#[allow(clippy::module_inception)]
 pub mod eventline;
+pub mod activity;
+pub mod changes;
+pub mod channel;
+pub mod columns;
+pub mod facets;
+pub mod field;
+pub mod filter;
+pub mod index;
+pub mod listed;
+pub mod rows;
+pub mod search;
+pub mod severity;
+pub mod sort;
+pub mod source;
+pub mod terminal;
+pub mod timeline;
 pub mod ui;
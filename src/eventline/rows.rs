@@ -0,0 +1,63 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::eventline::Event;
+
+/// Text of one displayed event: its presentation for the list view and its field
+/// values for the table view.
+#[derive(Debug)]
+pub struct RowText {
+    pub presentation: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl RowText {
+    fn new(event: &(dyn Event + Send)) -> Self {
+        RowText {
+            presentation: event.get_event_presentation(),
+            fields: event
+                .get_event_fields()
+                .into_iter()
+                .map(|field| (field.name, field.value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Value of the field called name, empty when the event has none.
+    pub fn field(&self, name: &str) -> &str {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map_or("", |(_, value)| value.as_str())
+    }
+}
+
+/// Text of the events by key, formatted the first time a row is drawn or searched
+/// and kept until the event changes, so a frame only formats the rows coming into view.
+#[derive(Debug, Default)]
+pub struct RowCache {
+    // filled while drawing, which only borrows the viewer
+    rows: RefCell<HashMap<String, Arc<RowText>>>,
+}
+
+impl RowCache {
+    /// Text of event stored under key.
+    pub fn get(&self, key: &str, event: &(dyn Event + Send)) -> Arc<RowText> {
+        if let Some(row) = self.rows.borrow().get(key) {
+            return row.clone();
+        }
+        let row = Arc::new(RowText::new(event));
+        self.rows.borrow_mut().insert(key.to_string(), row.clone());
+        row
+    }
+
+    /// Forgets the text of key, its event changed or is gone.
+    pub fn invalidate(&mut self, key: &str) {
+        self.rows.get_mut().remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.get_mut().clear();
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use super::filter::regex_error;
+
+/// How the search query is matched against the event rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Substring,
+    IgnoreCase,
+    Regex,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Substring => SearchMode::IgnoreCase,
+            SearchMode::IgnoreCase => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Substring,
+        }
+    }
+}
+
+impl fmt::Display for SearchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchMode::Substring => "substring",
+            SearchMode::IgnoreCase => "ignore case",
+            SearchMode::Regex => "regex",
+        })
+    }
+}
+
+/// A compiled search query. Every mode is compiled to a regex,
+/// substring queries are escaped first.
+#[derive(Debug, Clone)]
+pub struct Search {
+    query: String,
+    mode: SearchMode,
+    regex: Regex,
+}
+
+impl Search {
+    pub fn new(query: &str, mode: SearchMode) -> Result<Self, String> {
+        let pattern = match mode {
+            SearchMode::Regex => query.to_string(),
+            SearchMode::Substring | SearchMode::IgnoreCase => regex::escape(query),
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(mode == SearchMode::IgnoreCase)
+            .build()
+            .map_err(|e| format!("invalid regex: {}", regex_error(&e)))?;
+        Ok(Search {
+            query: query.to_string(),
+            mode,
+            regex,
+        })
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn mode(&self) -> SearchMode {
+        self.mode
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Byte ranges of the non-empty matches in text.
+    pub fn spans(&self, text: &str) -> Vec<(usize, usize)> {
+        self.regex
+            .find_iter(text)
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+}
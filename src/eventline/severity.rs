@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// How important an event is, from the least to the most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Severity {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 6] = [
+        Severity::Trace,
+        Severity::Debug,
+        Severity::Info,
+        Severity::Warn,
+        Severity::Error,
+        Severity::Fatal,
+    ];
+
+    /// Position in ALL.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Next more severe level, the most severe one stays.
+    pub fn raise(self) -> Self {
+        Severity::ALL[(self.index() + 1).min(Severity::ALL.len() - 1)]
+    }
+
+    /// Next less severe level, the least severe one stays.
+    pub fn lower(self) -> Self {
+        Severity::ALL[self.index().saturating_sub(1)]
+    }
+
+    /// Reads a level name in any case, with the usual aliases like "warning",
+    /// "err" or the syslog "crit", "alert" and "emerg".
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "trace" | "trc" => Some(Severity::Trace),
+            "debug" | "dbg" => Some(Severity::Debug),
+            "info" | "inf" | "information" | "notice" => Some(Severity::Info),
+            "warn" | "wrn" | "warning" => Some(Severity::Warn),
+            "error" | "err" => Some(Severity::Error),
+            "fatal" | "ftl" | "critical" | "crit" | "alert" | "emerg" | "panic" => Some(Severity::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Level of the numbers used by bunyan and pino: 10 trace to 60 fatal.
+    pub fn from_number(level: i64) -> Option<Self> {
+        match level {
+            10 => Some(Severity::Trace),
+            20 => Some(Severity::Debug),
+            30 => Some(Severity::Info),
+            40 => Some(Severity::Warn),
+            50 => Some(Severity::Error),
+            60 => Some(Severity::Fatal),
+            _ => None,
+        }
+    }
+
+    /// First upper case level name standing as a word in text, like the ERROR of
+    /// "2024-01-02 10:00:00 ERROR [main] failed". Lower case words are too common to tell.
+    pub fn find_in(text: &str) -> Option<Self> {
+        text.split(|c: char| !c.is_ascii_alphabetic())
+            .filter(|word| word.len() >= 3 && word.bytes().all(|b| b.is_ascii_uppercase()))
+            .find_map(Severity::parse)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Trace => "trace",
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+            Severity::Fatal => "fatal",
+        };
+        write!(f, "{}", name)
+    }
+}
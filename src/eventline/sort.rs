@@ -0,0 +1,280 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::eventline::Event;
+use super::field::FieldValue;
+
+/// What the events are compared on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    /// The key of the event in the events map.
+    Key,
+    /// The time returned by get_event_time.
+    Time,
+    /// A structured field, events without it sort last.
+    Field(String),
+}
+
+/// Direction of a sort term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn flip(self) -> Self {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+
+    /// Arrow shown next to a sorted column.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "▲",
+            SortOrder::Desc => "▼",
+        }
+    }
+}
+
+/// One level of a sort, with its order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortTerm {
+    pub key: SortKey,
+    pub order: SortOrder,
+}
+
+/// Ordered list of sort terms, later terms break ties of earlier ones.
+/// Events still equal after every term are ordered by key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    terms: Vec<SortTerm>,
+}
+
+impl Default for SortSpec {
+    fn default() -> Self {
+        SortSpec::by(SortKey::Key, SortOrder::Asc)
+    }
+}
+
+impl SortSpec {
+    pub fn by(key: SortKey, order: SortOrder) -> Self {
+        SortSpec {
+            terms: vec![SortTerm { key, order }],
+        }
+    }
+
+    /// The single term of a one level sort.
+    pub fn single(&self) -> Option<&SortTerm> {
+        match self.terms.as_slice() {
+            [term] => Some(term),
+            _ => None,
+        }
+    }
+
+    /// Order of the term sorting on key, if any.
+    pub fn order_of(&self, key: &SortKey) -> Option<SortOrder> {
+        self.terms.iter().find(|t| t.key == *key).map(|t| t.order)
+    }
+
+    /// Cycles the preset modes: key, time ascending, time descending.
+    pub fn cycle_preset(&mut self) {
+        *self = match self.single() {
+            Some(SortTerm { key: SortKey::Key, .. }) => SortSpec::by(SortKey::Time, SortOrder::Asc),
+            Some(SortTerm {
+                key: SortKey::Time,
+                order: SortOrder::Asc,
+            }) => SortSpec::by(SortKey::Time, SortOrder::Desc),
+            _ => SortSpec::by(SortKey::Key, SortOrder::Asc),
+        }
+    }
+
+    /// Makes key the only sort term, or flips its order when it already is the primary term.
+    pub fn sort_by(&mut self, key: SortKey) {
+        match self.terms.first() {
+            Some(term) if term.key == key => *self = SortSpec::by(key, term.order.flip()),
+            _ => *self = SortSpec::by(key, SortOrder::Asc),
+        }
+    }
+
+    /// Adds key as the last tie-breaker, or flips its order when it is already a term.
+    pub fn then_by(&mut self, key: SortKey) {
+        match self.terms.iter_mut().find(|t| t.key == key) {
+            Some(term) => term.order = term.order.flip(),
+            None => self.terms.push(SortTerm {
+                key,
+                order: SortOrder::Asc,
+            }),
+        }
+    }
+
+    /// Computes where event goes in this order, looking up each sort value once.
+    pub fn position_of(&self, key: &str, event: &(dyn Event + Send)) -> SortPosition {
+        SortPosition {
+            values: self.values_of(key, event),
+            key: key.to_string(),
+        }
+    }
+
+    /// True when event would still be at position in this order.
+    pub fn is_at(&self, position: &SortPosition, event: &(dyn Event + Send)) -> bool {
+        self.terms.iter().zip(&position.values).all(|(term, current)| {
+            match (self.value_of(term, &position.key, event), &current.value) {
+                (Some(value), Some(current)) => value.cmp_value(current).is_eq(),
+                (None, None) => true,
+                _ => false,
+            }
+        })
+    }
+
+    fn values_of(&self, key: &str, event: &(dyn Event + Send)) -> Vec<DirectedValue> {
+        self.terms
+            .iter()
+            .map(|term| DirectedValue {
+                value: self.value_of(term, key, event),
+                desc: term.order == SortOrder::Desc,
+            })
+            .collect()
+    }
+
+    fn value_of(&self, term: &SortTerm, key: &str, event: &(dyn Event + Send)) -> Option<FieldValue> {
+        match &term.key {
+            SortKey::Key => Some(FieldValue::Str(key.to_string())),
+            SortKey::Time => Some(FieldValue::Time(event.get_event_time())),
+            SortKey::Field(name) => event.get_event_field(name),
+        }
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|term| {
+                let name = match &term.key {
+                    SortKey::Key => "key",
+                    SortKey::Time => "time",
+                    SortKey::Field(name) => name.as_str(),
+                };
+                format!("{} {}", name, term.order.symbol())
+            })
+            .collect();
+        f.write_str(&terms.join(", "))
+    }
+}
+
+/// Sort value of one term along with its direction.
+#[derive(Debug, Clone)]
+struct DirectedValue {
+    value: Option<FieldValue>,
+    desc: bool,
+}
+
+impl Ord for DirectedValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.value, &other.value) {
+            (Some(a), Some(b)) if self.desc => b.cmp_value(a),
+            (Some(a), Some(b)) => a.cmp_value(b),
+            // missing values go last whatever the order
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+impl PartialOrd for DirectedValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for DirectedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DirectedValue {}
+
+/// Place of an event in a SortSpec order: the values of every term,
+/// then the event key so that no two events compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortPosition {
+    values: Vec<DirectedValue>,
+    key: String,
+}
+
+impl SortPosition {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::source::json::{self, JsonMapping};
+
+    /// Keys in the order of spec of events given as key and JSON object.
+    fn sorted(spec: &SortSpec, events: &[(&str, &str)]) -> Vec<String> {
+        let mut positions: Vec<SortPosition> = events
+            .iter()
+            .map(|(key, line)| {
+                let event = json::line_event("test", 0, line.to_string(), &JsonMapping::default());
+                spec.position_of(key, event.as_ref())
+            })
+            .collect();
+        positions.sort();
+        positions.iter().map(|position| position.key().to_string()).collect()
+    }
+
+    #[test]
+    fn descending_reverses_the_values() {
+        let events = [("a", r#"{"n": 2}"#), ("b", r#"{"n": 10}"#), ("c", r#"{"n": 1}"#)];
+        let field = || SortKey::Field(String::from("n"));
+        assert_eq!(sorted(&SortSpec::by(field(), SortOrder::Asc), &events), ["c", "a", "b"]);
+        assert_eq!(sorted(&SortSpec::by(field(), SortOrder::Desc), &events), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn missing_values_sort_last_in_both_orders() {
+        let events = [("a", r#"{}"#), ("b", r#"{"n": 1}"#), ("c", r#"{"n": 2}"#), ("d", r#"{"m": 0}"#)];
+        let field = || SortKey::Field(String::from("n"));
+        assert_eq!(sorted(&SortSpec::by(field(), SortOrder::Asc), &events), ["b", "c", "a", "d"]);
+        assert_eq!(sorted(&SortSpec::by(field(), SortOrder::Desc), &events), ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn then_by_breaks_ties() {
+        let events = [
+            ("a", r#"{"host": "y", "n": 1}"#),
+            ("b", r#"{"host": "x", "n": 2}"#),
+            ("c", r#"{"host": "y", "n": 3}"#),
+            ("d", r#"{"host": "x", "n": 1}"#),
+        ];
+        let mut spec = SortSpec::by(SortKey::Field(String::from("host")), SortOrder::Asc);
+        // equal hosts are left in key order
+        assert_eq!(sorted(&spec, &events), ["b", "d", "a", "c"]);
+        spec.then_by(SortKey::Field(String::from("n")));
+        assert_eq!(sorted(&spec, &events), ["d", "b", "a", "c"]);
+        // a second then_by flips the tie-breaker only
+        spec.then_by(SortKey::Field(String::from("n")));
+        assert_eq!(spec.to_string(), "host ▲, n ▼");
+        assert_eq!(sorted(&spec, &events), ["b", "d", "c", "a"]);
+    }
+
+    #[test]
+    fn sort_by_flips_the_primary_term() {
+        let mut spec = SortSpec::by(SortKey::Time, SortOrder::Asc);
+        spec.then_by(SortKey::Key);
+        spec.sort_by(SortKey::Time);
+        assert_eq!(spec, SortSpec::by(SortKey::Time, SortOrder::Desc));
+        spec.sort_by(SortKey::Key);
+        assert_eq!(spec, SortSpec::default());
+    }
+}
@@ -0,0 +1,232 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+use super::LineEvent;
+use crate::eventline::eventline::Event;
+use crate::eventline::field::{Field, FieldValue};
+use crate::eventline::filter::parse_time;
+use crate::eventline::severity::Severity;
+
+/// Keys of a JSON object giving the time, the id and the severity of its event.
+/// Nested keys are written with dots, like "log.time".
+#[derive(Debug, Clone)]
+pub struct JsonMapping {
+    /// The first of these keys present gives the event time.
+    pub time_keys: Vec<String>,
+    /// Gives the event id, events with the same id replace each other.
+    pub id_key: String,
+    /// The first of these keys holding a level name or number gives the severity.
+    pub level_keys: Vec<String>,
+}
+
+impl Default for JsonMapping {
+    fn default() -> Self {
+        JsonMapping {
+            time_keys: vec![String::from("timestamp"), String::from("time"), String::from("ts")],
+            id_key: String::from("id"),
+            level_keys: vec![String::from("level"), String::from("severity"), String::from("log.level")],
+        }
+    }
+}
+
+/// Event read from a JSON object, one field per value with nested objects flattened.
+#[derive(Debug, Clone)]
+pub struct JsonEvent {
+    fields: Vec<Field>,
+    time: DateTime<Utc>,
+    id: String,
+    severity: Severity,
+}
+
+impl Event for JsonEvent {
+    fn get_event_fields(&self) -> Vec<Field> {
+        self.fields.clone()
+    }
+
+    fn get_event_field(&self, name: &str) -> Option<FieldValue> {
+        self.fields.iter().find(|field| field.name == name).map(|field| field.value.clone())
+    }
+
+    fn get_event_time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn get_event_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn get_event_severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn clone_dyn(&self) -> Box<dyn Event + Send> {
+        Box::new(self.clone())
+    }
+}
+
+/// Event of a line: a JsonEvent when the line is a JSON object, the raw text otherwise.
+pub fn line_event(source: &str, seq: u64, line: String, mapping: &JsonMapping) -> Box<dyn Event + Send> {
+    let line_event = LineEvent::new(source, seq, line);
+    if !line_event.line().trim_start().starts_with('{') {
+        return Box::new(line_event);
+    }
+    let Ok(Value::Object(object)) = serde_json::from_str::<Value>(line_event.line()) else {
+        return Box::new(line_event);
+    };
+
+    let mut fields = Vec::new();
+    flatten("", object, &mut fields);
+    // the time field becomes a time, so it sorts and filters as one
+    let mut time = None;
+    for key in &mapping.time_keys {
+        if let Some(field) = fields.iter_mut().find(|f| f.name == *key)
+            && let Some(parsed) = to_time(&field.value)
+        {
+            field.value = FieldValue::Time(parsed);
+            time = Some(parsed);
+            break;
+        }
+    }
+    let id = match fields.iter().find(|f| f.name == mapping.id_key) {
+        Some(field) => field.value.to_string(),
+        None => line_event.get_event_id(),
+    };
+    let severity = mapping
+        .level_keys
+        .iter()
+        .filter_map(|key| fields.iter().find(|f| f.name == *key))
+        .find_map(|field| match &field.value {
+            FieldValue::Str(name) => Severity::parse(name),
+            FieldValue::Int(level) => Severity::from_number(*level),
+            _ => None,
+        })
+        .unwrap_or_default();
+    Box::new(JsonEvent {
+        fields,
+        time: time.unwrap_or_else(|| line_event.get_event_time()),
+        id,
+        severity,
+    })
+}
+
+/// Appends a field for every value of object, nested keys joined with dots.
+fn flatten(prefix: &str, object: Map<String, Value>, fields: &mut Vec<Field>) {
+    for (key, value) in object {
+        let name = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+        let value = match value {
+            Value::Object(nested) => {
+                flatten(&name, nested, fields);
+                continue;
+            }
+            Value::String(s) => FieldValue::Str(s),
+            Value::Bool(b) => FieldValue::Bool(b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => FieldValue::Int(i),
+                None => FieldValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::Null => FieldValue::Str(String::new()),
+            // arrays are kept as JSON text
+            array @ Value::Array(_) => FieldValue::Str(array.to_string()),
+        };
+        fields.push(Field::new(name, value));
+    }
+}
+
+/// Time of a string or of a Unix timestamp in seconds, or milliseconds when too large for seconds.
+fn to_time(value: &FieldValue) -> Option<DateTime<Utc>> {
+    // later than year 5138 in seconds is taken as milliseconds
+    const MILLIS: f64 = 1e11;
+    let seconds = match value {
+        FieldValue::Str(s) => return parse_time(s),
+        FieldValue::Time(t) => return Some(*t),
+        FieldValue::Int(i) => *i as f64,
+        FieldValue::Float(f) => *f,
+        FieldValue::Bool(_) => return None,
+    };
+    let seconds = if seconds.abs() >= MILLIS { seconds / 1000.0 } else { seconds };
+    DateTime::from_timestamp_millis((seconds * 1000.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> Box<dyn Event + Send> {
+        line_event("test", 7, line.to_string(), &JsonMapping::default())
+    }
+
+    #[test]
+    fn flattens_nested_objects_with_dots() {
+        let event = event(r#"{"a": {"b": 1, "c": {"d": "x"}}, "e": [1, 2], "f": null, "g": 1.5, "h": true}"#);
+        let fields = event.get_event_fields();
+        let names: Vec<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["a.b", "a.c.d", "e", "f", "g", "h"]);
+        assert_eq!(event.get_event_field("a.b"), Some(FieldValue::Int(1)));
+        assert_eq!(event.get_event_field("a.c.d"), Some(FieldValue::Str(String::from("x"))));
+        assert_eq!(event.get_event_field("e"), Some(FieldValue::Str(String::from("[1,2]"))));
+        assert_eq!(event.get_event_field("f"), Some(FieldValue::Str(String::new())));
+        assert_eq!(event.get_event_field("g"), Some(FieldValue::Float(1.5)));
+        assert_eq!(event.get_event_field("h"), Some(FieldValue::Bool(true)));
+        assert_eq!(event.get_event_field("a"), None);
+    }
+
+    #[test]
+    fn first_time_key_that_parses_gives_the_time() {
+        let time = parse_time("2024-05-01T10:00:00Z").unwrap();
+        // "timestamp" does not parse, "time" comes before "ts"
+        let event = event(r#"{"ts": 0, "time": "2024-05-01T10:00:00Z", "timestamp": "soon"}"#);
+        assert_eq!(event.get_event_time(), time);
+        assert_eq!(event.get_event_field("time"), Some(FieldValue::Time(time)));
+        assert_eq!(event.get_event_field("ts"), Some(FieldValue::Int(0)));
+        assert_eq!(event.get_event_field("timestamp"), Some(FieldValue::Str(String::from("soon"))));
+
+        let mapping = JsonMapping { time_keys: vec![String::from("log.at")], ..JsonMapping::default() };
+        let event = line_event("test", 0, r#"{"log": {"at": "2024-05-01 10:00:00"}}"#.to_string(), &mapping);
+        assert_eq!(event.get_event_time(), time);
+    }
+
+    #[test]
+    fn to_time_takes_large_numbers_as_milliseconds() {
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(to_time(&FieldValue::Int(1_700_000_000)), Some(time));
+        assert_eq!(to_time(&FieldValue::Int(1_700_000_000_000)), Some(time));
+        assert_eq!(
+            to_time(&FieldValue::Float(1_700_000_000.25)),
+            DateTime::from_timestamp_millis(1_700_000_000_250)
+        );
+        assert_eq!(to_time(&FieldValue::Str(String::from("2023-11-14"))), parse_time("2023-11-14"));
+        assert_eq!(to_time(&FieldValue::Str(String::from("yesterday"))), None);
+        assert_eq!(to_time(&FieldValue::Bool(true)), None);
+    }
+
+    #[test]
+    fn id_key_gives_the_id() {
+        assert_eq!(event(r#"{"id": 42}"#).get_event_id(), "42");
+        assert_eq!(event(r#"{"id": "a-1"}"#).get_event_id(), "a-1");
+        // without one, the id of the line keeps the order of arrival
+        assert_eq!(event(r#"{"other": 1}"#).get_event_id(), "test:0000000007");
+    }
+
+    #[test]
+    fn level_key_gives_the_severity() {
+        assert_eq!(event(r#"{"level": "WARN"}"#).get_event_severity(), Severity::Warn);
+        assert_eq!(event(r#"{"level": 50}"#).get_event_severity(), Severity::Error);
+        assert_eq!(event(r#"{"log": {"level": "debug"}}"#).get_event_severity(), Severity::Debug);
+        // a level that is not one is skipped for the next key
+        assert_eq!(event(r#"{"level": "loud", "severity": "fatal"}"#).get_event_severity(), Severity::Fatal);
+        assert_eq!(event(r#"{"level": 3}"#).get_event_severity(), Severity::default());
+    }
+
+    #[test]
+    fn other_lines_stay_text() {
+        for line in ["plain text", "{not json", "[1, 2]", r#"{"a": 1} trailing"#] {
+            let event = event(line);
+            assert!(event.as_any().is::<LineEvent>(), "{}", line);
+            assert_eq!(event.get_event_field("line"), Some(FieldValue::Str(line.to_string())));
+        }
+    }
+}
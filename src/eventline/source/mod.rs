@@ -0,0 +1,109 @@
+pub mod json;
+pub mod stdin;
+pub mod syslog;
+pub mod tail;
+
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use super::channel::EventSender;
+use super::eventline::{Event, EventMessage};
+use super::field::{Field, FieldValue};
+use super::severity::Severity;
+use json::JsonMapping;
+
+/// One line of text read by a source, numbered in order of arrival.
+#[derive(Debug, Clone)]
+pub struct LineEvent {
+    source: String,
+    seq: u64,
+    received: DateTime<Utc>,
+    line: String,
+    severity: Severity,
+}
+
+impl LineEvent {
+    /// Line received now from source.
+    pub fn new(source: &str, seq: u64, line: String) -> Self {
+        LineEvent {
+            source: source.to_string(),
+            seq,
+            received: Utc::now(),
+            severity: Severity::find_in(&line).unwrap_or_default(),
+            line,
+        }
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+impl Event for LineEvent {
+    fn get_event_fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("seq", self.seq),
+            Field::new("received", self.received),
+            Field::new("source", self.source.as_str()),
+            Field::new("line", self.line.as_str()),
+        ]
+    }
+
+    fn get_event_presentation(&self) -> String {
+        format!("{} {}", FieldValue::Time(self.received), self.line)
+    }
+
+    fn get_event_time(&self) -> DateTime<Utc> {
+        self.received
+    }
+
+    fn get_event_severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn get_event_id(&self) -> String {
+        // zero padded so that sorting by key keeps the order of arrival
+        format!("{}:{:010}", self.source, self.seq)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn clone_dyn(&self) -> Box<dyn Event + Send> {
+        Box::new(self.clone())
+    }
+}
+
+/// Sends every line of reader as an event of source, until the end of the input.
+/// JSON objects become JsonEvents, other lines LineEvents. Lines that are not
+/// valid UTF-8 are kept with the invalid bytes replaced.
+/// Returns how many lines were read.
+pub async fn read_lines<R: AsyncBufRead + Unpin>(
+    source: &str,
+    mut reader: R,
+    mapping: &JsonMapping,
+    sender: &EventSender,
+) -> Result<u64, String> {
+    let mut buffer = Vec::new();
+    let mut seq = 0;
+    loop {
+        buffer.clear();
+        let read = reader
+            .read_until(b'\n', &mut buffer)
+            .await
+            .map_err(|e| format!("error reading {}: {}", source, e))?;
+        if read == 0 {
+            return Ok(seq);
+        }
+        let line = String::from_utf8_lossy(&buffer);
+        let line = line.trim_end_matches(['\n', '\r']).to_string();
+        seq += 1;
+        sender.send(EventMessage::upsert(json::line_event(source, seq, line, mapping))).await?;
+    }
+}
+
+/// Reports the state of source in the viewer header.
+pub async fn send_status(sender: &EventSender, source: &str, status: impl Into<String>) -> Result<(), String> {
+    sender.send(EventMessage::Status(source.to_string(), status.into())).await
+}
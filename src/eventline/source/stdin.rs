@@ -0,0 +1,66 @@
+use std::io::Read;
+
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::sync::oneshot;
+
+use super::json::JsonMapping;
+use super::{read_lines, send_status};
+use crate::eventline::channel::EventSender;
+
+/// Name of the source, shown in the header and in the event ids.
+pub const STDIN: &str = "stdin";
+
+// Bytes read from stdin waiting to be turned into events
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// Turns every line piped to the process into an event, as in `some-service | eventline`.
+/// The viewer keeps using the terminal, crossterm reads the keyboard from /dev/tty
+/// when stdin is not a terminal. The header shows "closed" once the input ends.
+pub async fn read_stdin(mapping: JsonMapping, sender: EventSender) -> Result<(), String> {
+    send_status(&sender, STDIN, "reading").await?;
+    let (reader, read_error) = spawn_reader();
+    match read_lines(STDIN, BufReader::new(reader), &mapping, &sender).await {
+        Ok(lines) => match read_error.await {
+            Ok(Err(e)) => {
+                let e = format!("error reading {}: {}", STDIN, e);
+                send_status(&sender, STDIN, e.clone()).await?;
+                Err(e)
+            }
+            _ => send_status(&sender, STDIN, format!("closed after {} lines", lines)).await,
+        },
+        Err(e) => {
+            send_status(&sender, STDIN, e.clone()).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Copies stdin to a pipe from a thread of its own: a blocking read cannot be
+/// cancelled, and the runtime would wait for it when the viewer quits before the
+/// input ends. The thread stops once the pipe reader is dropped, the error ending
+/// the input, if any, comes through the oneshot.
+fn spawn_reader() -> (tokio::io::DuplexStream, oneshot::Receiver<std::io::Result<()>>) {
+    let (reader, mut writer) = tokio::io::duplex(PIPE_SIZE);
+    let (error_tx, error_rx) = oneshot::channel();
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin().lock();
+        let mut chunk = vec![0; PIPE_SIZE];
+        let result = loop {
+            match stdin.read(&mut chunk) {
+                Ok(0) => break Ok(()),
+                Ok(read) => {
+                    // fails when nobody reads the events anymore
+                    if runtime.block_on(writer.write_all(&chunk[..read])).is_err() {
+                        break Ok(());
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        let _ = error_tx.send(result);
+        // dropping the writer ends the input of read_lines
+    });
+    (reader, error_rx)
+}
@@ -0,0 +1,526 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Utc};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use super::send_status;
+use crate::eventline::channel::EventSender;
+use crate::eventline::eventline::{Event, EventMessage};
+use crate::eventline::field::{Field, FieldValue};
+use crate::eventline::severity::Severity;
+
+/// Largest message accepted, longer TCP frames close the connection.
+const MAX_MESSAGE: usize = 64 * 1024;
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp", "ntp",
+    "security", "console", "solaris-cron", "local0", "local1", "local2", "local3", "local4", "local5", "local6",
+    "local7",
+];
+
+const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+/// Where the receiver listens, each address is optional.
+#[derive(Debug, Clone, Default)]
+pub struct SyslogOptions {
+    pub udp: Option<SocketAddr>,
+    pub tcp: Option<SocketAddr>,
+}
+
+/// Syslog format a message was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFormat {
+    /// BSD syslog: `<PRI>Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`
+    Rfc3164,
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID [SD] MSG`
+    Rfc5424,
+}
+
+/// Structured data element of an RFC 5424 message: `[id name="value" ...]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdElement {
+    pub id: String,
+    pub params: Vec<(String, String)>,
+}
+
+/// A received syslog message. Values missing from the message are empty.
+#[derive(Debug, Clone)]
+pub struct SyslogEvent {
+    pub format: SyslogFormat,
+    pub priority: u8,
+    pub timestamp: DateTime<Utc>,
+    pub hostname: String,
+    pub app_name: String,
+    pub procid: String,
+    pub msgid: String,
+    // as written in the message, also parsed in sd_elements
+    pub structured_data: String,
+    pub sd_elements: Vec<SdElement>,
+    pub message: String,
+    pub peer: String,
+    seq: u64,
+}
+
+impl SyslogEvent {
+    pub fn facility(&self) -> &'static str {
+        FACILITIES.get((self.priority >> 3) as usize).copied().unwrap_or("unknown")
+    }
+
+    pub fn severity(&self) -> &'static str {
+        SEVERITIES[(self.priority & 7) as usize]
+    }
+}
+
+impl Event for SyslogEvent {
+    fn get_event_fields(&self) -> Vec<Field> {
+        let mut fields = vec![
+            Field::new("timestamp", self.timestamp),
+            Field::new("facility", self.facility()),
+            Field::new("severity", self.severity()),
+            Field::new("hostname", self.hostname.as_str()),
+            Field::new("app_name", self.app_name.as_str()),
+            Field::new("procid", self.procid.as_str()),
+            Field::new("msgid", self.msgid.as_str()),
+            Field::new("message", self.message.as_str()),
+            Field::new("structured_data", self.structured_data.as_str()),
+            Field::new("peer", self.peer.as_str()),
+        ];
+        for element in &self.sd_elements {
+            for (name, value) in &element.params {
+                fields.push(Field::new(format!("{}.{}", element.id, name), value.as_str()));
+            }
+        }
+        fields
+    }
+
+    fn get_event_presentation(&self) -> String {
+        let mut text = format!("{} ", FieldValue::Time(self.timestamp));
+        if !self.hostname.is_empty() {
+            text.push_str(&format!("{} ", self.hostname));
+        }
+        text.push_str(&format!("{}.{} ", self.facility(), self.severity()));
+        if !self.app_name.is_empty() {
+            text.push_str(&self.app_name);
+            if !self.procid.is_empty() {
+                text.push_str(&format!("[{}]", self.procid));
+            }
+            text.push_str(": ");
+        }
+        text.push_str(&self.message);
+        text
+    }
+
+    fn get_event_time(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn get_event_severity(&self) -> Severity {
+        match self.priority & 7 {
+            0..=2 => Severity::Fatal,
+            3 => Severity::Error,
+            4 => Severity::Warn,
+            5 | 6 => Severity::Info,
+            _ => Severity::Debug,
+        }
+    }
+
+    fn get_event_id(&self) -> String {
+        format!("syslog:{:010}", self.seq)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn clone_dyn(&self) -> Box<dyn Event + Send> {
+        Box::new(self.clone())
+    }
+}
+
+/// Receives syslog messages on the configured UDP and TCP addresses until the
+/// viewer is gone. Every listener reports its state in the header.
+pub async fn listen(options: SyslogOptions, sender: EventSender) -> Result<(), String> {
+    let seq = Arc::new(AtomicU64::new(0));
+    let mut tasks = Vec::new();
+    if let Some(address) = options.udp {
+        tasks.push(tokio::spawn(listen_udp(address, seq.clone(), sender.clone())));
+    }
+    if let Some(address) = options.tcp {
+        tasks.push(tokio::spawn(listen_tcp(address, seq.clone(), sender.clone())));
+    }
+    for task in tasks {
+        task.await.map_err(|e| format!("syslog listener failed: {}", e))??;
+    }
+    Ok(())
+}
+
+async fn listen_udp(address: SocketAddr, seq: Arc<AtomicU64>, sender: EventSender) -> Result<(), String> {
+    let name = format!("syslog udp {}", address);
+    let socket = match UdpSocket::bind(address).await {
+        Ok(socket) => socket,
+        Err(e) => return send_status(&sender, &name, format!("error: {}", e)).await,
+    };
+    send_status(&sender, &name, "listening").await?;
+    let mut buffer = vec![0; MAX_MESSAGE];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => return stop_listening(&sender, &name, format!("error receiving: {}", e)).await,
+        };
+        let text = String::from_utf8_lossy(&buffer[..len]);
+        send_message(&text, &peer.to_string(), &seq, &sender).await?;
+    }
+}
+
+async fn listen_tcp(address: SocketAddr, seq: Arc<AtomicU64>, sender: EventSender) -> Result<(), String> {
+    let name = format!("syslog tcp {}", address);
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => return send_status(&sender, &name, format!("error: {}", e)).await,
+    };
+    send_status(&sender, &name, "listening").await?;
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => return stop_listening(&sender, &name, format!("error accepting: {}", e)).await,
+        };
+        let seq = seq.clone();
+        let sender = sender.clone();
+        // a broken connection only ends itself
+        tokio::spawn(async move {
+            let _ = read_tcp(stream, peer.to_string(), seq, sender).await;
+        });
+    }
+}
+
+/// Shows why the listener called name stopped, then returns the error.
+async fn stop_listening(sender: &EventSender, name: &str, error: String) -> Result<(), String> {
+    send_status(sender, name, error.clone()).await?;
+    Err(format!("{} on {}", error, name))
+}
+
+/// Reads the messages of a connection, framed either by octet counting
+/// (`LEN SP MSG`) or by line feeds, as described in RFC 6587.
+async fn read_tcp(stream: TcpStream, peer: String, seq: Arc<AtomicU64>, sender: EventSender) -> Result<(), String> {
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::new();
+    loop {
+        let buffer = reader.fill_buf().await.map_err(|e| e.to_string())?;
+        let Some(first) = buffer.first() else {
+            return Ok(());
+        };
+        frame.clear();
+        if first.is_ascii_digit() {
+            // the count and its space, a longer run of digits is no count
+            let mut len = Vec::new();
+            let limit = MAX_MESSAGE.to_string().len() as u64 + 1;
+            (&mut reader).take(limit).read_until(b' ', &mut len).await.map_err(|e| e.to_string())?;
+            let len: usize = std::str::from_utf8(&len)
+                .ok()
+                .and_then(|len| len.strip_suffix(' '))
+                .and_then(|len| len.parse().ok())
+                .filter(|len| *len <= MAX_MESSAGE)
+                .ok_or_else(|| String::from("bad octet count"))?;
+            frame.resize(len, 0);
+            reader.read_exact(&mut frame).await.map_err(|e| e.to_string())?;
+        } else {
+            // one byte more than a message tells a line that is too long
+            let limit = MAX_MESSAGE as u64 + 1;
+            (&mut reader).take(limit).read_until(b'\n', &mut frame).await.map_err(|e| e.to_string())?;
+            if frame.len() > MAX_MESSAGE && frame.last() != Some(&b'\n') {
+                return Err(format!("message longer than {} bytes", MAX_MESSAGE));
+            }
+        }
+        let text = String::from_utf8_lossy(&frame);
+        let text = text.trim_end_matches(['\n', '\r', '\0']);
+        if !text.is_empty() {
+            send_message(text, &peer, &seq, &sender).await?;
+        }
+    }
+}
+
+async fn send_message(text: &str, peer: &str, seq: &AtomicU64, sender: &EventSender) -> Result<(), String> {
+    let mut event = parse_message(text, Utc::now());
+    event.peer = peer.to_string();
+    event.seq = seq.fetch_add(1, Ordering::Relaxed) + 1;
+    sender.send(EventMessage::upsert(Box::new(event))).await
+}
+
+/// Parses a message in either format. A message without a valid priority is
+/// kept whole as the text of a user.notice message, as RFC 3164 asks.
+pub fn parse_message(text: &str, received: DateTime<Utc>) -> SyslogEvent {
+    let mut event = SyslogEvent {
+        format: SyslogFormat::Rfc3164,
+        priority: 13,
+        timestamp: received,
+        hostname: String::new(),
+        app_name: String::new(),
+        procid: String::new(),
+        msgid: String::new(),
+        structured_data: String::new(),
+        sd_elements: Vec::new(),
+        message: String::new(),
+        peer: String::new(),
+        seq: 0,
+    };
+    let Some((priority, rest)) = parse_priority(text) else {
+        event.message = text.to_string();
+        return event;
+    };
+    event.priority = priority;
+    match rest.strip_prefix("1 ") {
+        Some(rest) => parse_5424(rest, &mut event),
+        None => parse_3164(rest, &mut event),
+    }
+    event
+}
+
+/// `<PRI>` at the start of text, with the text after it.
+fn parse_priority(text: &str) -> Option<(u8, &str)> {
+    let rest = text.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let digits = &rest[..end];
+    if digits.is_empty() || digits.len() > 3 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let priority: u8 = digits.parse().ok().filter(|p| *p <= 191)?;
+    Some((priority, &rest[end + 1..]))
+}
+
+fn parse_5424(text: &str, event: &mut SyslogEvent) {
+    event.format = SyslogFormat::Rfc5424;
+    let mut parts = text.splitn(5, ' ');
+    let mut next = || match parts.next() {
+        Some("-") | None => String::new(),
+        Some(part) => part.to_string(),
+    };
+    let timestamp = next();
+    if let Ok(time) = DateTime::parse_from_rfc3339(&timestamp) {
+        event.timestamp = time.with_timezone(&Utc);
+    }
+    event.hostname = next();
+    event.app_name = next();
+    event.procid = next();
+    let rest = parts.next().unwrap_or_default();
+    let (msgid, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    event.msgid = if msgid == "-" { String::new() } else { msgid.to_string() };
+
+    let message = match parse_structured_data(rest) {
+        Some((elements, message)) => {
+            event.structured_data = rest[..rest.len() - message.len()].trim_end().to_string();
+            if event.structured_data == "-" {
+                event.structured_data.clear();
+            }
+            event.sd_elements = elements;
+            message
+        }
+        // malformed structured data is left in the message
+        None => rest,
+    };
+    event.message = message.strip_prefix(' ').unwrap_or(message).trim_start_matches('\u{feff}').to_string();
+}
+
+/// Structured data at the start of text, either "-" or a list of elements,
+/// with the text after it.
+fn parse_structured_data(text: &str) -> Option<(Vec<SdElement>, &str)> {
+    if let Some(rest) = text.strip_prefix('-') {
+        return Some((Vec::new(), rest));
+    }
+    let mut elements = Vec::new();
+    let mut rest = text;
+    while let Some(element) = rest.strip_prefix('[') {
+        let end = element.find([' ', ']'])?;
+        let mut sd = SdElement {
+            id: element[..end].to_string(),
+            params: Vec::new(),
+        };
+        rest = &element[end..];
+        while let Some(param) = rest.strip_prefix(' ') {
+            let (name, value) = param.split_once("=\"")?;
+            let mut unescaped = String::new();
+            let mut chars = value.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (_, '\\') => {
+                        let (_, c) = chars.next()?;
+                        // only '"', '\' and ']' are escaped, other backslashes are kept
+                        if !matches!(c, '"' | '\\' | ']') {
+                            unescaped.push('\\');
+                        }
+                        unescaped.push(c);
+                    }
+                    (i, '"') => break i,
+                    (_, c) => unescaped.push(c),
+                }
+            };
+            sd.params.push((name.to_string(), unescaped));
+            rest = &value[end + 1..];
+        }
+        rest = rest.strip_prefix(']')?;
+        elements.push(sd);
+    }
+    if elements.is_empty() { None } else { Some((elements, rest)) }
+}
+
+fn parse_3164(text: &str, event: &mut SyslogEvent) {
+    // "Mmm dd hh:mm:ss ", the day padded with a space
+    let Some(time) = text.get(..15).and_then(|stamp| parse_3164_time(stamp, event.timestamp)) else {
+        // without a valid timestamp the whole text is the message (RFC 3164 4.3.3)
+        event.message = text.to_string();
+        return;
+    };
+    event.timestamp = time;
+    let mut rest = text[15..].trim_start();
+    // the hostname is missing when the first word already is the tag
+    if let Some((first, after)) = rest.split_once(' ')
+        && !first.ends_with(':')
+        && !first.contains('[')
+    {
+        event.hostname = first.to_string();
+        rest = after;
+    }
+    // TAG[PID]: MSG, the tag is at most 32 alphanumeric characters
+    let tag_end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')))
+        .unwrap_or(rest.len());
+    let after_tag = &rest[tag_end..];
+    let (procid, message) = match after_tag.strip_prefix('[').and_then(|p| p.split_once("]:")) {
+        Some((procid, message)) => (procid, Some(message)),
+        None => ("", after_tag.strip_prefix(':')),
+    };
+    match message {
+        Some(message) if tag_end > 0 && tag_end <= 32 => {
+            event.app_name = rest[..tag_end].to_string();
+            event.procid = procid.to_string();
+            event.message = message.strip_prefix(' ').unwrap_or(message).to_string();
+        }
+        _ => event.message = rest.to_string(),
+    }
+}
+
+/// Local time without a year, taken in the year that does not put it in the future.
+fn parse_3164_time(stamp: &str, received: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let stamp = stamp.split_whitespace().collect::<Vec<_>>().join(" ");
+    let year = received.with_timezone(&Local).year();
+    let parse = |year: i32| {
+        let time = NaiveDateTime::parse_from_str(&format!("{} {}", year, stamp), "%Y %b %d %H:%M:%S").ok()?;
+        Local.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc))
+    };
+    let time = parse(year)?;
+    // December messages received in January
+    if time > received + chrono::Duration::days(1) { parse(year - 1) } else { Some(time) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received() -> DateTime<Utc> {
+        Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn rfc3164() {
+        let event = parse_message("<34>Oct 11 22:14:15 mymachine su[42]: 'su root' failed", received());
+        assert_eq!(event.format, SyslogFormat::Rfc3164);
+        assert_eq!((event.facility(), event.severity()), ("auth", "crit"));
+        assert_eq!(event.hostname, "mymachine");
+        assert_eq!((event.app_name.as_str(), event.procid.as_str()), ("su", "42"));
+        assert_eq!(event.message, "'su root' failed");
+        // no year in the message, October is later than June so it was last year
+        let expected = Local.with_ymd_and_hms(2023, 10, 11, 22, 14, 15).unwrap();
+        assert_eq!(event.timestamp, expected.with_timezone(&Utc));
+    }
+
+    #[test]
+    fn rfc3164_without_hostname() {
+        let event = parse_message("<13>Jun  1 11:00:00 cron: job done", received());
+        assert_eq!(event.hostname, "");
+        assert_eq!(event.app_name, "cron");
+        assert_eq!(event.procid, "");
+        assert_eq!(event.message, "job done");
+
+        let event = parse_message("<13>Jun  1 11:00:00 sshd[7]: closed", received());
+        assert_eq!((event.hostname.as_str(), event.app_name.as_str()), ("", "sshd"));
+    }
+
+    #[test]
+    fn rfc3164_without_timestamp_keeps_the_text() {
+        let event = parse_message("<13>just some text", received());
+        assert_eq!(event.hostname, "");
+        assert_eq!(event.app_name, "");
+        assert_eq!(event.message, "just some text");
+        assert_eq!(event.timestamp, received());
+
+        let event = parse_message("<13>su[42]: 'su root' failed", received());
+        assert_eq!((event.hostname.as_str(), event.app_name.as_str()), ("", ""));
+        assert_eq!(event.message, "su[42]: 'su root' failed");
+    }
+
+    #[test]
+    fn rfc3164_without_tag_keeps_the_text() {
+        let event = parse_message("<13>Jun  1 11:00:00 myhost some text", received());
+        assert_eq!(event.hostname, "myhost");
+        assert_eq!(event.app_name, "");
+        assert_eq!(event.message, "some text");
+    }
+
+    #[test]
+    fn without_priority() {
+        let event = parse_message("<999>not syslog", received());
+        assert_eq!((event.facility(), event.severity()), ("user", "notice"));
+        assert_eq!(event.message, "<999>not syslog");
+    }
+
+    #[test]
+    fn rfc5424_with_structured_data() {
+        let text = r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application"][examplePriority@32473 class="high \"x\" \]"] An application event"#;
+        let event = parse_message(text, received());
+        assert_eq!(event.format, SyslogFormat::Rfc5424);
+        assert_eq!((event.facility(), event.severity()), ("local4", "notice"));
+        assert_eq!(event.timestamp, "2003-10-11T22:14:15.003Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(event.hostname, "mymachine.example.com");
+        assert_eq!(event.app_name, "evntslog");
+        assert_eq!(event.procid, "");
+        assert_eq!(event.msgid, "ID47");
+        assert!(event.structured_data.starts_with("[exampleSDID@32473 ") && event.structured_data.ends_with(r#"\]"]"#));
+        assert_eq!(
+            event.sd_elements,
+            vec![
+                SdElement {
+                    id: String::from("exampleSDID@32473"),
+                    params: vec![
+                        (String::from("iut"), String::from("3")),
+                        (String::from("eventSource"), String::from("Application")),
+                    ],
+                },
+                SdElement {
+                    id: String::from("examplePriority@32473"),
+                    params: vec![(String::from("class"), String::from(r#"high "x" ]"#))],
+                },
+            ]
+        );
+        assert_eq!(event.message, "An application event");
+        assert_eq!(event.get_event_field("exampleSDID@32473.iut"), Some(FieldValue::from("3")));
+    }
+
+    #[test]
+    fn rfc5424_without_structured_data_and_with_bom() {
+        let event = parse_message("<14>1 - host app 12 - - \u{feff}hello", received());
+        assert_eq!(event.timestamp, received());
+        assert_eq!((event.hostname.as_str(), event.procid.as_str(), event.msgid.as_str()), ("host", "12", ""));
+        assert_eq!(event.structured_data, "");
+        assert!(event.sd_elements.is_empty());
+        assert_eq!(event.message, "hello");
+    }
+
+    #[test]
+    fn rfc5424_malformed_structured_data_stays_in_the_message() {
+        let event = parse_message(r#"<14>1 - host app - - [id name="unterminated] text"#, received());
+        assert!(event.sd_elements.is_empty());
+        assert_eq!(event.message, r#"[id name="unterminated] text"#);
+    }
+}
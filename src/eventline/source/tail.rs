@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::json::{self, JsonMapping};
+use super::send_status;
+use crate::eventline::channel::EventSender;
+use crate::eventline::eventline::EventMessage;
+
+// Bytes read from a file at once, the lines of each chunk are sent as one batch
+const READ_CHUNK: usize = 1024 * 1024;
+
+/// How the files are followed.
+#[derive(Debug, Clone)]
+pub struct TailOptions {
+    /// Delay between two checks of the files.
+    pub poll_interval: Duration,
+    /// Read the files present at start from their beginning instead of their end.
+    /// Files created later are always read from the beginning.
+    pub from_start: bool,
+    /// File where the read offsets are kept, to resume where the previous run stopped.
+    pub offsets: Option<PathBuf>,
+    /// How lines holding JSON objects become events.
+    pub json: JsonMapping,
+}
+
+impl Default for TailOptions {
+    fn default() -> Self {
+        TailOptions {
+            poll_interval: Duration::from_millis(250),
+            from_start: false,
+            offsets: None,
+            json: JsonMapping::default(),
+        }
+    }
+}
+
+/// Follows the files like `tail -F`, sending every new line as a LineEvent named
+/// after its file. A rotated file (same path, new inode) is read to its end before
+/// the new one is opened, a truncated file is read again from its beginning and
+/// a missing file is waited for. The state of every file is shown in the header.
+/// Runs until the viewer is gone.
+pub async fn tail_files(paths: Vec<PathBuf>, options: TailOptions, sender: EventSender) -> Result<(), String> {
+    let mut offsets = match &options.offsets {
+        Some(path) => match OffsetStore::load(path) {
+            Ok(offsets) => offsets,
+            // nothing is followed, the header tells why
+            Err(e) => {
+                send_status(&sender, &offsets_source(path), e.clone()).await?;
+                return Err(e);
+            }
+        },
+        None => OffsetStore::default(),
+    };
+    // last error saving the offsets, shown until a save works again
+    let mut save_error: Option<String> = None;
+    let mut files: Vec<TailedFile> = paths.into_iter().map(TailedFile::new).collect();
+    for file in files.iter_mut() {
+        file.resume = offsets.get(&file.path);
+        file.from_start = options.from_start;
+    }
+
+    let mut interval = tokio::time::interval(options.poll_interval);
+    while !sender.is_closed() {
+        interval.tick().await;
+        let mut moved = false;
+        for file in files.iter_mut() {
+            match file.poll(&options.json, &sender).await {
+                Ok(moved_file) => moved |= moved_file,
+                // the viewer is gone
+                Err(_) if sender.is_closed() => return Ok(()),
+                // the file is opened again on the next check, where it was left
+                Err(e) => {
+                    file.resume = file.position().or(file.resume);
+                    file.file = None;
+                    file.set_status(&sender, e).await?;
+                }
+            }
+            if let Some(position) = file.position() {
+                offsets.set(&file.path, position);
+            }
+        }
+        if moved && let Some(path) = &options.offsets {
+            // the files are still followed, only the positions are not kept
+            match offsets.save(path) {
+                Ok(()) if save_error.is_some() => {
+                    save_error = None;
+                    send_status(&sender, &offsets_source(path), "saved").await?;
+                }
+                Ok(()) => {}
+                Err(e) if save_error.as_ref() != Some(&e) => {
+                    send_status(&sender, &offsets_source(path), e.clone()).await?;
+                    save_error = Some(e);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of the offsets file in the header.
+fn offsets_source(path: &Path) -> String {
+    format!("offsets {}", path.display())
+}
+
+/// Inode and offset of the first byte not read yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    inode: u64,
+    offset: u64,
+}
+
+struct TailedFile {
+    path: PathBuf,
+    // source name of the events
+    name: String,
+    file: Option<File>,
+    inode: u64,
+    // offset after the last complete line
+    offset: u64,
+    // end of the file read without its line feed yet
+    pending: Vec<u8>,
+    seq: u64,
+    status: String,
+    // where the previous run stopped, used when the file is first opened
+    resume: Option<Position>,
+    from_start: bool,
+    // files seen only after start are read from their beginning
+    first_open: bool,
+}
+
+impl TailedFile {
+    fn new(path: PathBuf) -> Self {
+        TailedFile {
+            name: path.display().to_string(),
+            path,
+            file: None,
+            inode: 0,
+            offset: 0,
+            pending: Vec::new(),
+            seq: 0,
+            status: String::new(),
+            resume: None,
+            from_start: false,
+            first_open: true,
+        }
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.file.as_ref().map(|_| Position {
+            inode: self.inode,
+            offset: self.offset,
+        })
+    }
+
+    /// Checks the file once, sending the new lines. Returns true when the offset moved.
+    async fn poll(&mut self, mapping: &JsonMapping, sender: &EventSender) -> Result<bool, String> {
+        let before = self.position();
+        let metadata = tokio::fs::metadata(&self.path).await.ok();
+        match (self.file.is_some(), metadata) {
+            (false, None) => {
+                self.first_open = false;
+                self.set_status(sender, "waiting").await?;
+            }
+            (false, Some(metadata)) => {
+                self.open(sender, inode(&metadata), metadata.len()).await?;
+                self.read(mapping, sender).await?;
+            }
+            // moved away, the rest of the old file is read while waiting for the new one
+            (true, None) => {
+                self.read(mapping, sender).await?;
+                self.flush_pending(mapping, sender).await?;
+                self.file = None;
+                self.first_open = false;
+                self.set_status(sender, "rotated, waiting").await?;
+            }
+            (true, Some(metadata)) if inode(&metadata) != self.inode => {
+                self.read(mapping, sender).await?;
+                self.flush_pending(mapping, sender).await?;
+                self.first_open = false;
+                self.open(sender, inode(&metadata), metadata.len()).await?;
+                self.set_status(sender, format!("following, rotated at {}", chrono::Local::now().format("%H:%M:%S")))
+                    .await?;
+                self.read(mapping, sender).await?;
+            }
+            (true, Some(metadata)) if metadata.len() < self.offset + self.pending.len() as u64 => {
+                self.seek(0).await?;
+                self.set_status(sender, format!("following, truncated at {}", chrono::Local::now().format("%H:%M:%S")))
+                    .await?;
+                self.read(mapping, sender).await?;
+            }
+            (true, Some(_)) => {
+                self.read(mapping, sender).await?;
+            }
+        }
+        Ok(self.position() != before)
+    }
+
+    /// Opens the file, at the stored offset when resuming it, at its end when
+    /// following a file present at start, otherwise at its beginning.
+    async fn open(&mut self, sender: &EventSender, inode: u64, len: u64) -> Result<(), String> {
+        let file = File::open(&self.path)
+            .await
+            .map_err(|e| format!("error opening {}: {}", self.name, e))?;
+        let offset = match self.resume.take() {
+            Some(position) if position.inode == inode && position.offset <= len => position.offset,
+            _ if self.first_open && !self.from_start => len,
+            _ => 0,
+        };
+        self.file = Some(file);
+        self.inode = inode;
+        self.first_open = false;
+        self.seek(offset).await?;
+        self.set_status(sender, "following").await
+    }
+
+    async fn seek(&mut self, offset: u64) -> Result<(), String> {
+        if let Some(file) = self.file.as_mut() {
+            file.seek(SeekFrom::Start(offset))
+                .await
+                .map_err(|e| format!("error reading {}: {}", self.name, e))?;
+        }
+        self.offset = offset;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Sends the complete lines written since the last read, READ_CHUNK bytes at a time
+    /// so that a large file is never held in memory whole.
+    async fn read(&mut self, mapping: &JsonMapping, sender: &EventSender) -> Result<(), String> {
+        loop {
+            let Some(file) = self.file.as_mut() else {
+                return Ok(());
+            };
+            let mut data = std::mem::take(&mut self.pending);
+            let read = file
+                .take(READ_CHUNK as u64)
+                .read_to_end(&mut data)
+                .await
+                .map_err(|e| format!("error reading {}: {}", self.name, e))?;
+            let mut complete = data.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            // a line longer than a chunk is sent in pieces
+            if complete == 0 && data.len() >= READ_CHUNK {
+                complete = data.len();
+            }
+            self.pending = data.split_off(complete);
+            self.offset += complete as u64;
+            let mut lines: Vec<&[u8]> = data.split(|b| *b == b'\n').collect();
+            // the slice after the last line feed is empty
+            if lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+            self.send_lines(&lines, mapping, sender).await?;
+            // the end of the file
+            if read < READ_CHUNK {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends the end of a file that will not be written anymore, even without its line feed.
+    async fn flush_pending(&mut self, mapping: &JsonMapping, sender: &EventSender) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        self.offset += pending.len() as u64;
+        self.send_lines(&[&pending], mapping, sender).await
+    }
+
+    async fn send_lines(&mut self, lines: &[&[u8]], mapping: &JsonMapping, sender: &EventSender) -> Result<(), String> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut batch = Vec::with_capacity(lines.len());
+        for line in lines {
+            let line = String::from_utf8_lossy(line).trim_end_matches('\r').to_string();
+            self.seq += 1;
+            let event = json::line_event(&self.name, self.seq, line, mapping);
+            batch.push((event.get_event_id(), event));
+        }
+        sender.send(EventMessage::Batch(batch)).await
+    }
+
+    /// Reports status in the header when it changed.
+    async fn set_status(&mut self, sender: &EventSender, status: impl Into<String>) -> Result<(), String> {
+        let status = status.into();
+        if status == self.status {
+            return Ok(());
+        }
+        self.status = status.clone();
+        send_status(sender, &self.name, status).await
+    }
+}
+
+#[cfg(unix)]
+fn inode(metadata: &std::fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+// without inodes a rotation is only seen as a truncation
+#[cfg(not(unix))]
+fn inode(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+/// Read positions of the followed files, stored one per line as "inode offset path".
+#[derive(Debug, Default)]
+struct OffsetStore {
+    positions: HashMap<PathBuf, Position>,
+}
+
+impl OffsetStore {
+    /// Reads the stored positions, a missing file is an empty store.
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(OffsetStore::default()),
+            Err(e) => return Err(format!("error reading offsets {}: {}", path.display(), e)),
+        };
+        let mut positions = HashMap::new();
+        for line in text.lines() {
+            let mut parts = line.splitn(3, ' ');
+            if let (Some(inode), Some(offset), Some(file)) = (parts.next(), parts.next(), parts.next())
+                && let (Ok(inode), Ok(offset)) = (inode.parse(), offset.parse())
+            {
+                positions.insert(PathBuf::from(file), Position { inode, offset });
+            }
+        }
+        Ok(OffsetStore { positions })
+    }
+
+    fn get(&self, file: &Path) -> Option<Position> {
+        self.positions.get(file).copied()
+    }
+
+    fn set(&mut self, file: &Path, position: Position) {
+        self.positions.insert(file.to_path_buf(), position);
+    }
+
+    /// Writes the positions next to path then renames it over path,
+    /// so a crash never leaves a partial file.
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let text: String = self
+            .positions
+            .iter()
+            .map(|(file, position)| format!("{} {} {}\n", position.inode, position.offset, file.display()))
+            .collect();
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, text)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|e| format!("error writing offsets {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventline::channel::{EventReceiver, OverflowPolicy, event_channel};
+    use crate::eventline::field::FieldValue;
+
+    /// Empty directory of its own for a test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("eventline-tail-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn append(path: &Path, text: &str) {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn options(from_start: bool) -> TailOptions {
+        TailOptions {
+            poll_interval: Duration::from_millis(10),
+            from_start,
+            ..TailOptions::default()
+        }
+    }
+
+    /// Follows path, returning the receiving end of the events.
+    fn follow(path: &Path, options: TailOptions) -> EventReceiver {
+        let (sender, receiver) = event_channel(None, OverflowPolicy::Block);
+        tokio::spawn(tail_files(vec![path.to_path_buf()], options, sender));
+        receiver
+    }
+
+    /// Lines received until count of them arrived, statuses apart.
+    async fn lines(receiver: &mut EventReceiver, count: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        while lines.len() < count {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .expect("no line within 5s")
+                .expect("tailing stopped");
+            if let EventMessage::Batch(events) = message {
+                for (_, event) in events {
+                    match event.get_event_field("line") {
+                        Some(FieldValue::Str(line)) => lines.push(line),
+                        other => panic!("unexpected line {:?}", other),
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    /// Waits for the status of the followed file to start with prefix.
+    async fn status(receiver: &mut EventReceiver, prefix: &str) {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap_or_else(|_| panic!("no status '{}' within 5s", prefix))
+                .expect("tailing stopped");
+            if let EventMessage::Status(_, status) = message
+                && status.starts_with(prefix)
+            {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_appended_lines() {
+        let dir = TempDir::new("append");
+        let path = dir.0.join("app.log");
+        append(&path, "old\n");
+        let mut receiver = follow(&path, options(false));
+        status(&mut receiver, "following").await;
+        append(&path, "one\ntwo\nthr");
+        assert_eq!(lines(&mut receiver, 2).await, ["one", "two"]);
+        append(&path, "ee\n");
+        assert_eq!(lines(&mut receiver, 1).await, ["three"]);
+    }
+
+    #[tokio::test]
+    async fn reads_again_after_a_truncation() {
+        let dir = TempDir::new("truncate");
+        let path = dir.0.join("app.log");
+        append(&path, "first line\nsecond line\n");
+        let mut receiver = follow(&path, options(true));
+        assert_eq!(lines(&mut receiver, 2).await, ["first line", "second line"]);
+        std::fs::write(&path, "new\n").unwrap();
+        status(&mut receiver, "following, truncated").await;
+        assert_eq!(lines(&mut receiver, 1).await, ["new"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn finishes_a_rotated_file_then_follows_the_new_one() {
+        let dir = TempDir::new("rotate");
+        let path = dir.0.join("app.log");
+        append(&path, "a\n");
+        let mut receiver = follow(&path, options(true));
+        assert_eq!(lines(&mut receiver, 1).await, ["a"]);
+        // written just before the rotation, without its line feed
+        append(&path, "b\nlast");
+        std::fs::rename(&path, dir.0.join("app.log.1")).unwrap();
+        append(&path, "c\n");
+        assert_eq!(lines(&mut receiver, 3).await, ["b", "last", "c"]);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_missing_file_then_reads_it_from_the_beginning() {
+        let dir = TempDir::new("late");
+        let path = dir.0.join("late.log");
+        let mut receiver = follow(&path, options(false));
+        status(&mut receiver, "waiting").await;
+        append(&path, "created
+");
+        assert_eq!(lines(&mut receiver, 1).await, ["created"]);
+    }
+
+    #[tokio::test]
+    async fn reads_a_large_file_in_chunks() {
+        let dir = TempDir::new("chunks");
+        let path = dir.0.join("big.log");
+        let line = "x".repeat(99);
+        let count = 3 * READ_CHUNK / 100 + 7;
+        append(&path, &format!("{}\n", line).repeat(count));
+        // longer than a chunk, sent in pieces
+        let long = 3 * READ_CHUNK;
+        append(&path, &format!("{}\n", "y".repeat(long)));
+
+        let (sender, mut receiver) = event_channel(None, OverflowPolicy::Block);
+        tokio::spawn(tail_files(vec![path.clone()], options(true), sender));
+        let (mut short, mut pieces, mut batches) = (0, Vec::new(), 0);
+        while pieces.iter().sum::<usize>() < long {
+            let message = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+            if let EventMessage::Batch(events) = message {
+                batches += 1;
+                assert!(events.len() <= READ_CHUNK / 100 + 1, "{} lines in a batch", events.len());
+                for (_, event) in events {
+                    match event.get_event_field("line") {
+                        Some(FieldValue::Str(l)) if l == line => short += 1,
+                        Some(FieldValue::Str(l)) if l.bytes().all(|b| b == b'y') => pieces.push(l.len()),
+                        other => panic!("unexpected line {:?}", other),
+                    }
+                }
+            }
+        }
+        assert_eq!(short, count);
+        assert!(pieces.len() >= 3 && pieces.iter().all(|len| *len <= 2 * READ_CHUNK), "{:?}", pieces);
+        assert!(batches >= 6);
+    }
+
+    #[test]
+    fn offsets_round_trip() {
+        let dir = TempDir::new("offsets");
+        let path = dir.0.join("offsets");
+        assert!(OffsetStore::load(&path).unwrap().positions.is_empty());
+
+        let mut offsets = OffsetStore::default();
+        offsets.set(Path::new("/var/log/app.log"), Position { inode: 42, offset: 1000 });
+        offsets.set(Path::new("/var/log/with space.log"), Position { inode: 7, offset: 3 });
+        offsets.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let loaded = OffsetStore::load(&path).unwrap();
+        assert_eq!(loaded.positions, offsets.positions);
+        assert_eq!(loaded.get(Path::new("/var/log/with space.log")), Some(Position { inode: 7, offset: 3 }));
+    }
+
+    #[test]
+    fn offsets_errors() {
+        let dir = TempDir::new("offsets-errors");
+        // a directory cannot be read nor replaced by a file
+        let error = OffsetStore::load(&dir.0).unwrap_err();
+        assert!(error.starts_with("error reading offsets "), "{}", error);
+        let error = OffsetStore::default().save(&dir.0.join("missing").join("offsets")).unwrap_err();
+        assert!(error.starts_with("error writing offsets "), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn resumes_at_the_saved_offset() {
+        let dir = TempDir::new("resume");
+        let path = dir.0.join("app.log");
+        let offsets = dir.0.join("offsets");
+        append(&path, "one\ntwo\n");
+        let with_offsets = || TailOptions {
+            offsets: Some(offsets.clone()),
+            ..options(true)
+        };
+        let mut receiver = follow(&path, with_offsets());
+        assert_eq!(lines(&mut receiver, 2).await, ["one", "two"]);
+        // stops the first run
+        drop(receiver);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        append(&path, "three\n");
+        let mut receiver = follow(&path, with_offsets());
+        assert_eq!(lines(&mut receiver, 1).await, ["three"]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::stdout;
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::execute;
+use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode};
+use ratatui::{DefaultTerminal, Frame, Terminal, backend::CrosstermBackend};
+
+// set while a TerminalGuard holds the terminal, the panic hook restores it only then
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HOOKS: Once = Once::new();
+
+/// The terminal in raw mode on the alternate screen, given back to the shell when
+/// dropped, whether the viewer quits, returns an error, panics or has its task aborted.
+/// Only one can exist at a time.
+pub struct TerminalGuard {
+    terminal: DefaultTerminal,
+}
+
+impl TerminalGuard {
+    /// Takes over the terminal, installing the color_eyre reports and a panic hook
+    /// that restores the terminal before reporting the panic.
+    pub fn new() -> Result<Self, String> {
+        if ACTIVE.swap(true, Ordering::SeqCst) {
+            return Err(String::from("error: the terminal is already in use"));
+        }
+        install_hooks();
+        let terminal = enable_raw_mode()
+            .and_then(|_| execute!(stdout(), EnterAlternateScreen))
+            .and_then(|_| Terminal::new(CrosstermBackend::new(stdout())));
+        match terminal {
+            Ok(terminal) => Ok(TerminalGuard { terminal }),
+            Err(e) => {
+                // whatever was already switched is switched back
+                restore();
+                Err(format!("error: {}", e))
+            }
+        }
+    }
+
+    pub fn draw<F: FnOnce(&mut Frame)>(&mut self, render: F) -> Result<(), String> {
+        self.terminal.draw(render).map(|_| ()).map_err(|e| format!("error: {}", e))
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leaves raw mode and the alternate screen if a guard holds them, only the first call does.
+fn restore() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        // nothing else to do when it fails, the shell may need a reset
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Installs color_eyre once per process. When the embedding app already installed
+/// its own hooks they are kept, the terminal is restored before its panic hook runs.
+fn install_hooks() {
+    HOOKS.call_once(|| {
+        let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default().into_hooks();
+        let panic_hook = match eyre_hook.install() {
+            Ok(_) => panic_hook.into_panic_hook(),
+            Err(_) => std::panic::take_hook(),
+        };
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            panic_hook(info);
+        }));
+    });
+}
+
+/// Signals asking the viewer to end: it would otherwise be killed with the terminal
+/// still in raw mode. Listening replaces their default action for the rest of the process.
+pub struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn new() -> Result<Self, String> {
+        use tokio::signal::unix::{SignalKind, signal};
+        let listen = |kind| signal(kind).map_err(|e| format!("error: {}", e));
+        Ok(Signals {
+            interrupt: listen(SignalKind::interrupt())?,
+            terminate: listen(SignalKind::terminate())?,
+            hangup: listen(SignalKind::hangup())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Result<Self, String> {
+        Ok(Signals {})
+    }
+
+    /// Waits for the next signal and returns its name.
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.interrupt.recv() => "SIGINT",
+            _ = self.terminate.recv() => "SIGTERM",
+            _ = self.hangup.recv() => "SIGHUP",
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn recv(&mut self) -> &'static str {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
@@ -0,0 +1,101 @@
+/// What the markers of the timeline are colored by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimelineColor {
+    Severity,
+    /// Value of the named field, the same value always gets the same color.
+    Field(String),
+}
+
+/// State of the timeline pane: the time range shown, in seconds since the epoch.
+#[derive(Debug)]
+pub struct Timeline {
+    visible: bool,
+    // None shows every event
+    range: Option<(f64, f64)>,
+    color: TimelineColor,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            visible: false,
+            range: None,
+            color: TimelineColor::Severity,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn color(&self) -> &TimelineColor {
+        &self.color
+    }
+
+    pub fn set_color(&mut self, color: TimelineColor) {
+        self.color = color;
+    }
+
+    /// True when only part of the events is shown.
+    pub fn zoomed(&self) -> bool {
+        self.range.is_some()
+    }
+
+    /// Range shown given the range of every event, which is widened when empty.
+    pub fn range(&self, full: (f64, f64)) -> (f64, f64) {
+        self.range.unwrap_or_else(|| widen(full))
+    }
+
+    /// Shrinks (factor < 1) or grows the range shown around the time at,
+    /// or around its middle. Growing past the full range shows every event.
+    pub fn zoom(&mut self, full: (f64, f64), factor: f64, at: Option<f64>) {
+        let (start, end) = self.range(full);
+        let at = at.filter(|at| (start..=end).contains(at)).unwrap_or((start + end) / 2.0);
+        // a millisecond is as close as it gets
+        let span = ((end - start) * factor).max(0.001);
+        let full = widen(full);
+        if span >= full.1 - full.0 {
+            self.range = None;
+            return;
+        }
+        // at keeps its place in the pane
+        let ratio = (at - start) / (end - start);
+        self.range = Some((at - span * ratio, at + span * (1.0 - ratio)));
+    }
+
+    /// Moves the range shown by fraction of its span, to the future when positive.
+    pub fn pan(&mut self, full: (f64, f64), fraction: f64) {
+        let (start, end) = self.range(full);
+        let shift = (end - start) * fraction;
+        self.range = Some((start + shift, end + shift));
+    }
+
+    /// Shows every event again.
+    pub fn fit(&mut self) {
+        self.range = None;
+    }
+
+    /// Pans just enough for time to be shown, used to keep the cursor in view.
+    pub fn keep_in_view(&mut self, time: f64) {
+        if let Some((start, end)) = self.range {
+            let span = end - start;
+            if time < start {
+                self.range = Some((time - span * 0.1, time + span * 0.9));
+            } else if time > end {
+                self.range = Some((time - span * 0.9, time + span * 0.1));
+            }
+        }
+    }
+}
+
+/// Range with a small margin, a single time getting a minute around it.
+fn widen((start, end): (f64, f64)) -> (f64, f64) {
+    let margin = ((end - start) * 0.02).max(if end > start { 0.0 } else { 30.0 });
+    (start - margin, end + margin)
+}
@@ -1,75 +1,507 @@
-use std::cell::RefCell;
+use std::cell::Cell;
+use super::activity;
+use super::changes::ChangeKind;
 use super::eventline;
+use super::field::FieldValue;
+use super::search::Search;
+use super::severity::Severity;
+use super::sort::SortKey;
+use super::terminal::TerminalGuard;
+use super::timeline::TimelineColor;
 
-use std::time::Duration;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 
 use ratatui::{
-    DefaultTerminal, Frame,
-    layout::{Constraint, Direction, Layout, Rect, Size},
-    style::{Style, Stylize},
-    text::{Text, Line, Span},
-    widgets::{Block, List, ListDirection, Paragraph},
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Cell as TableCell, Clear, List, ListDirection, ListItem, ListState, Paragraph, Row, Sparkline, Table, TableState, Wrap},
+    widgets::canvas::{Canvas, Line as CanvasLine, Points},
 };
 
-use crossterm::event::{Event as CEvent, KeyCode, poll};
-use ratatui::layout::Alignment;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// What the keyboard is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Normal,
+    // typing a search query after '/'
+    Search,
+    // moving in the facet panel
+    Facets,
+    // typing a command after ':'
+    Command,
+}
+
+/// User actions decoded from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    // closes the detail pane, or quits when nothing is open
+    Back,
+    ToggleDetail,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    // selects the last updated event as updates arrive, until the selection is moved
+    ToggleFollow,
+    // switches between the list and the table view
+    ToggleView,
+    // shows or hides the timeline pane
+    ToggleTimeline,
+    // zooms and pans the timeline, or shows every event again
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    FitTimeline,
+    // colors the timeline markers by severity or by the field of the current column
+    CycleTimelineColor,
+    // opens the facet panel on the field of the current column, or closes it
+    OpenFacets,
+    CloseFacets,
+    // moves in the facet panel and filters on the value under its cursor
+    FacetUp,
+    FacetDown,
+    ApplyFacet,
+    // hides one more or one less level of the least severe events
+    RaiseSeverity,
+    LowerSeverity,
+    ColumnLeft,
+    ColumnRight,
+    MoveColumnLeft,
+    MoveColumnRight,
+    HideColumn,
+    ShowColumns,
+    // key, time ascending, time descending
+    CycleSort,
+    // sorts on the column under the cursor, again to reverse
+    SortByColumn,
+    // adds the column under the cursor as a tie-breaker
+    ThenByColumn,
+    // opens the search prompt
+    StartSearch,
+    // opens the command prompt, prefilled with the current filter
+    StartCommand,
+    // prompt editing
+    Input(char),
+    DeleteChar,
+    CycleSearchMode,
+    Submit,
+    Cancel,
+    // lists only the matching rows, or all rows with highlighted matches
+    ToggleFilter,
+    NextMatch,
+    PrevMatch,
+}
 
 pub struct UI {
-    terminal: RefCell<DefaultTerminal>,
-    term_size: Size,
+    // first event row shown, kept between frames so the view only scrolls to follow the selection
+    offset: Cell<usize>,
+    // number of event rows visible in the last rendered frame
+    page_size: Cell<usize>,
 }
 
 impl UI {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let term = ratatui::init();
-        let size = term.size();
-        return UI{
-            terminal: RefCell::new(term),
-            term_size: size.unwrap(),
+        UI{
+            offset: Cell::new(0),
+            page_size: Cell::new(0),
         }
     }
 
-    pub fn render(&self, app: &eventline::EventLine) {
-        self.terminal.borrow_mut().draw(|frame| render(frame, app));
+    pub fn render(&self, terminal: &mut TerminalGuard, app: &eventline::EventLine) -> Result<(), String> {
+        terminal.draw(|frame| render(frame, app, self))
+    }
+
+    /// Number of rows the event list could display in the last frame.
+    pub fn page_size(&self) -> usize {
+        self.page_size.get()
     }
 }
 
-pub fn process_keypress() -> Result<bool, String> {
-    if let Some(key_code) = check_for_keypress()? {
-        match key_code {
-            KeyCode::Char('q') | KeyCode::Esc => {
-                return Ok(true);
-            }
-            _ => return Ok(false), // Ignore other keys
-        }
+/// Action of a key pressed in mode, if it has one.
+pub fn key_action(mode: InputMode, key: KeyEvent) -> Option<Action> {
+    // only key presses, some platforms also report releases
+    if key.kind != KeyEventKind::Press {
+        return None;
     }
-    return Ok(false);
+    // raw mode turns Ctrl-C into a key instead of SIGINT
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Some(Action::Quit);
+    }
+    match mode {
+        InputMode::Normal => {}
+        InputMode::Facets => return facet_action(key.code),
+        InputMode::Search | InputMode::Command => return prompt_action(key.code),
+    }
+    let action = match key.code {
+        KeyCode::Char('q') => Action::Quit,
+        KeyCode::Esc => Action::Back,
+        KeyCode::Enter => Action::ToggleDetail,
+        KeyCode::Up | KeyCode::Char('k') => Action::Up,
+        KeyCode::Down | KeyCode::Char('j') => Action::Down,
+        KeyCode::PageUp => Action::PageUp,
+        KeyCode::PageDown => Action::PageDown,
+        KeyCode::Home | KeyCode::Char('g') => Action::Home,
+        KeyCode::End | KeyCode::Char('G') => Action::End,
+        KeyCode::Char('F') => Action::ToggleFollow,
+        KeyCode::Char('t') => Action::ToggleView,
+        KeyCode::Char('T') => Action::ToggleTimeline,
+        KeyCode::Char('b') => Action::OpenFacets,
+        KeyCode::Char('z') => Action::ZoomIn,
+        KeyCode::Char('Z') => Action::ZoomOut,
+        KeyCode::Char('[') => Action::PanLeft,
+        KeyCode::Char(']') => Action::PanRight,
+        KeyCode::Char('0') => Action::FitTimeline,
+        KeyCode::Char('c') => Action::CycleTimelineColor,
+        KeyCode::Char('+') => Action::RaiseSeverity,
+        KeyCode::Char('-') => Action::LowerSeverity,
+        KeyCode::Left | KeyCode::Char('h') => Action::ColumnLeft,
+        KeyCode::Right | KeyCode::Char('l') => Action::ColumnRight,
+        KeyCode::Char('<') => Action::MoveColumnLeft,
+        KeyCode::Char('>') => Action::MoveColumnRight,
+        KeyCode::Char('x') => Action::HideColumn,
+        KeyCode::Char('X') => Action::ShowColumns,
+        KeyCode::Char('s') => Action::CycleSort,
+        KeyCode::Char('o') => Action::SortByColumn,
+        KeyCode::Char('O') => Action::ThenByColumn,
+        KeyCode::Char('/') => Action::StartSearch,
+        KeyCode::Char(':') => Action::StartCommand,
+        KeyCode::Char('f') => Action::ToggleFilter,
+        KeyCode::Char('n') => Action::NextMatch,
+        KeyCode::Char('N') => Action::PrevMatch,
+        _ => return None, // Ignore other keys
+    };
+    Some(action)
 }
-fn check_for_keypress() -> Result<Option<KeyCode>, String> {
-    if poll(Duration::from_millis(100)).map_err(|e| format!("error: {}", e))? {
-        match crossterm::event::read().map_err(|e| format!("error: {}", e))? {
-            CEvent::Key(key) => Ok(Some(key.code)),
-            _ => Ok(None),
-        }
-    } else {
-        // No event within timeout - this is normal behavior
-        Ok(None)
+
+/// Keys of the facet panel, the column keys choose its field.
+fn facet_action(key_code: KeyCode) -> Option<Action> {
+    match key_code {
+        KeyCode::Char('q') => Some(Action::Quit),
+        KeyCode::Esc | KeyCode::Char('b') => Some(Action::CloseFacets),
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::FacetUp),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::FacetDown),
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::ColumnLeft),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::ColumnRight),
+        KeyCode::Enter => Some(Action::ApplyFacet),
+        _ => None,
+    }
+}
+
+/// Keys while typing in the prompt.
+fn prompt_action(key_code: KeyCode) -> Option<Action> {
+    match key_code {
+        KeyCode::Char(c) => Some(Action::Input(c)),
+        KeyCode::Backspace => Some(Action::DeleteChar),
+        KeyCode::Tab => Some(Action::CycleSearchMode),
+        KeyCode::Enter => Some(Action::Submit),
+        KeyCode::Esc => Some(Action::Cancel),
+        _ => None,
     }
 }
 
-pub fn render(frame: &mut Frame, app: &eventline::EventLine) {
+pub fn render(frame: &mut Frame, app: &eventline::EventLine, ui: &UI) {
 
+    // one more header line when sources report their state
+    let header = 8 + u16::from(!app.sources().is_empty());
+    let timeline = if app.timeline().visible() { TIMELINE_HEIGHT } else { 0 };
     let l_layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Length(6), Constraint::Min(0)])
+        .constraints(vec![
+            Constraint::Length(header),
+            Constraint::Length(timeline),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
         .split(frame.area());
 
-    draw_list(frame, app, l_layout[1]);
+    // the facet panel takes the right of the events
+    let (events, facets) = if app.facets().field().is_some() {
+        let width = FACETS_WIDTH.min(l_layout[2].width / 2);
+        let split = Layout::horizontal([Constraint::Min(0), Constraint::Length(width)]).split(l_layout[2]);
+        (split[0], Some(split[1]))
+    } else {
+        (l_layout[2], None)
+    };
+    match app.view_mode() {
+        eventline::ViewMode::List => draw_list(frame, app, ui, events),
+        eventline::ViewMode::Table => draw_table(frame, app, ui, events),
+    }
+    if let Some(area) = facets {
+        draw_facets(frame, app, area);
+    }
     draw_title3(frame, app, l_layout[0]);
+    if app.timeline().visible() {
+        draw_timeline(frame, app, l_layout[1]);
+    }
+    draw_status(frame, app, l_layout[3]);
+
+    if app.detail_open() {
+        draw_detail(frame, app, l_layout[2]);
+    }
+}
+
+/// Width of the facet panel, borders included.
+const FACETS_WIDTH: u16 = 50;
+
+/// Most frequent values of the facet field with their count and share of the events.
+fn draw_facets(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
+    let facets = app.facets();
+    let total = app.events_map_size().max(1);
+    // value, then count and share aligned on the right
+    let numbers = 16;
+    let value_width = (area.width as usize).saturating_sub(numbers + 5);
+    let items: Vec<ListItem> = facets
+        .top()
+        .iter()
+        .map(|(value, count)| {
+            ListItem::new(format!(
+                "{:<value_width$} {:>8} {:>6.1}%",
+                truncate(&value.to_string(), value_width),
+                count,
+                *count as f64 * 100.0 / total as f64
+            ))
+        })
+        .collect();
+    let others = facets.distinct().saturating_sub(facets.top().len());
+    let mut block = Block::bordered()
+        .title(format!("{} ({} values)", facets.field().unwrap_or_default(), facets.distinct()))
+        .title_bottom(Line::from(" Enter filter  h/l field  Esc close ").centered());
+    if others > 0 {
+        block = block.title_bottom(Line::from(format!(" {} more ", others)).right_aligned());
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol(">");
+    let mut state = ListState::default().with_selected(Some(facets.cursor()));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Height of the timeline pane, borders included.
+const TIMELINE_HEIGHT: u16 = 8;
+
+/// Colors given to field values on the timeline.
+const FIELD_COLORS: [Color; 8] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::LightBlue,
+    Color::LightRed,
+    Color::White,
+    Color::LightGreen,
+];
+
+/// Listed events placed at their time on a horizontal axis, one lane per color,
+/// with a vertical cursor at the selected event.
+fn draw_timeline(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
+    let timeline = app.timeline();
+    let color_name = match timeline.color() {
+        TimelineColor::Severity => String::from("severity"),
+        TimelineColor::Field(name) => name.clone(),
+    };
+    let mut block = Block::bordered().title(format!(
+        "Timeline  color: {}  (z/Z zoom  [/] pan  0 fit  c color  T hide)",
+        color_name
+    ));
+    let Some(full) = app.time_range() else {
+        frame.render_widget(Paragraph::new("no events").block(block), area);
+        return;
+    };
+    let (start, end) = timeline.range(full);
+    let time = |seconds: f64| {
+        chrono::DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+            .map(|t| FieldValue::Time(t).to_string())
+            .unwrap_or_default()
+    };
+    block = block
+        .title_bottom(Line::from(time(start)).left_aligned())
+        .title_bottom(Line::from(span_label(end - start)).centered())
+        .title_bottom(Line::from(time(end)).right_aligned());
+
+    // markers grouped by color, a lane for each color
+    let lanes = match timeline.color() {
+        TimelineColor::Severity => Severity::ALL.len(),
+        TimelineColor::Field(_) => FIELD_COLORS.len(),
+    };
+    let mut points: Vec<Vec<(f64, f64)>> = vec![Vec::new(); lanes];
+    for key in app.data_keys().iter() {
+        let Some(event) = app.event(key) else {
+            continue;
+        };
+        let at = event.get_event_time().timestamp_millis() as f64 / 1000.0;
+        if at < start || at > end {
+            continue;
+        }
+        let lane = match timeline.color() {
+            TimelineColor::Severity => event.get_event_severity().index(),
+            TimelineColor::Field(name) => event.get_event_field(name).map_or(0, |value| {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                value.to_string().hash(&mut hasher);
+                hasher.finish() as usize % FIELD_COLORS.len()
+            }),
+        };
+        points[lane].push((at, lane as f64 + 0.5));
+    }
+    let lane_color = |lane: usize| match timeline.color() {
+        TimelineColor::Severity => {
+            let (r, g, b) = severity_color(Severity::ALL[lane]);
+            Color::Rgb(r, g, b)
+        }
+        TimelineColor::Field(_) => FIELD_COLORS[lane],
+    };
+    let cursor = app.selected_time();
+
+    let canvas = Canvas::default()
+        .block(block)
+        .marker(ratatui::symbols::Marker::Braille)
+        .x_bounds([start, end])
+        .y_bounds([0.0, lanes as f64])
+        .paint(|ctx| {
+            for (lane, coords) in points.iter().enumerate() {
+                ctx.draw(&Points {
+                    coords,
+                    color: lane_color(lane),
+                });
+            }
+            if let Some(at) = cursor {
+                ctx.layer();
+                ctx.draw(&CanvasLine {
+                    x1: at,
+                    y1: 0.0,
+                    x2: at,
+                    y2: lanes as f64,
+                    color: Color::Yellow,
+                });
+            }
+        });
+    frame.render_widget(canvas, area);
+}
+
+/// Span of the timeline in the largest unit fitting it, like "3.5d".
+fn span_label(seconds: f64) -> String {
+    const UNITS: [(f64, &str); 6] = [
+        (365.0 * 86400.0, "y"),
+        (86400.0, "d"),
+        (3600.0, "h"),
+        (60.0, "m"),
+        (1.0, "s"),
+        (0.001, "ms"),
+    ];
+    let (size, unit) = UNITS
+        .iter()
+        .copied()
+        .find(|(size, _)| seconds >= *size)
+        .unwrap_or(UNITS[UNITS.len() - 1]);
+    format!(" {:.1}{} ", seconds / size, unit)
+}
+
+/// Popup over the event list with one line per field of the selected event.
+fn draw_detail(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
+    let Some(event) = app.selected_event() else {
+        return;
+    };
+    let fields = event.get_event_fields();
+    let name_width = fields.iter().map(|field| field.name.len()).max().unwrap_or(0);
+    let text: Vec<Line> = fields
+        .into_iter()
+        .map(|field| {
+            Line::from(vec![
+                Span::styled(format!("{:<width$}  ", field.name, width = name_width), Style::new().green().italic()),
+                Span::raw(field.value.to_string()),
+            ])
+        })
+        .collect();
+
+    let popup = centered_rect(area, 80, 80);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(text)
+            .block(Block::bordered().title(format!("Event {}", event.get_event_id())))
+            .style(Style::new().white().on_black())
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+/// Returns a rect of the given percentage of area, centered in it.
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
-fn draw_list(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
-    let list = List::new(app.data_list().clone())
+/// Rows formatted on each side of the viewport, so scrolling a little finds them ready
+/// and the table columns keep their widths.
+const ROW_BUFFER: usize = 20;
+
+/// Rows of the page shown: the previous page, scrolled as little as possible to
+/// keep the selected row in view.
+fn viewport(app: &eventline::EventLine, ui: &UI, page: usize) -> Range<usize> {
+    let len = app.data_keys().len();
+    let mut offset = ui.offset.get().min(len.saturating_sub(page));
+    if let Some(selected) = app.selected() {
+        if selected < offset {
+            offset = selected;
+        } else if page > 0 && selected >= offset + page {
+            offset = selected + 1 - page;
+        }
+    }
+    ui.offset.set(offset);
+    offset..(offset + page).min(len)
+}
+
+/// Viewport with ROW_BUFFER more rows on each side.
+fn buffered(app: &eventline::EventLine, rows: &Range<usize>) -> Range<usize> {
+    rows.start.saturating_sub(ROW_BUFFER)..(rows.end + ROW_BUFFER).min(app.data_keys().len())
+}
+
+/// Selected row as an index in the viewport.
+fn selected_in(app: &eventline::EventLine, rows: &Range<usize>) -> Option<usize> {
+    app.selected().filter(|i| rows.contains(i)).map(|i| i - rows.start)
+}
+
+fn draw_list(frame: &mut Frame, app: &eventline::EventLine, ui: &UI, area: Rect) {
+    // the block borders take one row at the top and one at the bottom
+    let page = area.height.saturating_sub(2) as usize;
+    ui.page_size.set(page);
+
+    let rows = viewport(app, ui, page);
+    // formatted now so they are cached when scrolled into view
+    for key in app.data_keys().range(buffered(app, &rows)) {
+        app.row(key);
+    }
+    let items: Vec<ListItem> = rows
+        .clone()
+        .zip(app.data_keys().range(rows.clone()))
+        .map(|(i, key)| {
+            let text = app.row(key).map(|row| row.presentation.clone()).unwrap_or_default();
+            ListItem::new(highlight(&text, app.search())).style(row_style(app, i))
+        })
+        .collect();
+    let list = List::new(items)
         .block(Block::bordered().title("Events"))
         .style(Style::new().white())
         .highlight_style(Style::new().italic())
@@ -77,55 +509,382 @@ fn draw_list(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
         .repeat_highlight_symbol(true)
         .direction(ListDirection::TopToBottom);
 
-    frame.render_widget(list, area);
+    let mut state = ListState::default().with_selected(selected_in(app, &rows));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Widest a table column gets before its values are truncated.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+fn draw_table(frame: &mut Frame, app: &eventline::EventLine, ui: &UI, area: Rect) {
+    // borders plus the header row
+    let page = area.height.saturating_sub(3) as usize;
+    ui.page_size.set(page);
+
+    let columns: Vec<&str> = app.columns().visible().map(|c| c.name.as_str()).collect();
+    let current = app.columns().current();
+
+    // cell text of the rows around the viewport, in the order of the visible columns
+    let rows = viewport(app, ui, page);
+    let around = buffered(app, &rows);
+    let cells: Vec<Vec<String>> = app
+        .data_keys()
+        .range(around.clone())
+        .map(|key| {
+            let row = app.row(key);
+            columns
+                .iter()
+                .map(|name| row.as_ref().map(|row| row.field(name).to_string()).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    // a column is as wide as its widest value around the viewport or header, up to MAX_COLUMN_WIDTH
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                // room for the sort arrow after the name
+                .chain(std::iter::once(name.chars().count() + 1))
+                .max()
+                .unwrap_or(0)
+                .min(MAX_COLUMN_WIDTH)
+        })
+        .collect();
+    // borders, highlight symbol and one space between columns
+    let available = (area.width as usize)
+        .saturating_sub(4)
+        .saturating_sub(columns.len().saturating_sub(1));
+    let widths = fit_widths(widths, available);
+
+    let header = Row::new(columns.iter().zip(&widths).map(|(name, width)| {
+        let style = if Some(*name) == current {
+            Style::new().green().bold().reversed()
+        } else {
+            Style::new().green().bold()
+        };
+        let label = match app.sort().order_of(&SortKey::Field(name.to_string())) {
+            Some(order) => format!("{}{}", name, order.symbol()),
+            None => name.to_string(),
+        };
+        TableCell::from(truncate(&label, *width)).style(style)
+    }));
+    let shown = cells[rows.start - around.start..rows.end - around.start].iter().zip(rows.clone()).map(|(row, i)| {
+        Row::new(row.iter().zip(&widths).map(|(value, width)| {
+            TableCell::from(highlight(&truncate(value, *width), app.search()))
+        }))
+        .style(row_style(app, i))
+    });
+
+    let table = Table::new(shown, widths.iter().map(|w| Constraint::Length(*w as u16)))
+        .header(header)
+        .block(Block::bordered().title("Events"))
+        .style(Style::new().white())
+        .row_highlight_style(Style::new().italic())
+        .highlight_symbol(">>");
+
+    let mut state = TableState::default().with_selected(selected_in(app, &rows));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+/// Style of the row at index: colored by severity, added and modified events are
+/// colored, fading back to their severity color as the change gets older, removed
+/// ones are struck through. The last updated event stands out.
+fn row_style(app: &eventline::EventLine, index: usize) -> Style {
+    let Some(key) = app.data_keys().get(index) else {
+        return Style::new();
+    };
+    let severity = app.event(key).map(|event| event.get_event_severity()).unwrap_or_default();
+    let color = severity_color(severity);
+    let style = match app.change(key) {
+        Some((ChangeKind::Added, faded)) => Style::new().fg(fade((80, 230, 80), color, faded)),
+        Some((ChangeKind::Modified, faded)) => Style::new().fg(fade((80, 200, 255), color, faded)),
+        Some((ChangeKind::Removed, _)) => Style::new().dark_gray().crossed_out(),
+        None => Style::new().fg(Color::Rgb(color.0, color.1, color.2)),
+    };
+    if app.last_update_index() == Some(index) || severity == Severity::Fatal {
+        style.bold()
+    } else {
+        style
+    }
+}
+
+/// Text color of the events of severity.
+fn severity_color(severity: Severity) -> (u8, u8, u8) {
+    match severity {
+        Severity::Trace => (120, 120, 120),
+        Severity::Debug => (150, 160, 190),
+        Severity::Info => (255, 255, 255),
+        Severity::Warn => (250, 200, 60),
+        Severity::Error => (250, 80, 70),
+        Severity::Fatal => (240, 60, 220),
+    }
+}
+
+/// Color between from (faded = 0) and to (faded = 1).
+fn fade(from: (u8, u8, u8), to: (u8, u8, u8), faded: f32) -> Color {
+    let faded = faded.clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * faded) as u8;
+    Color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Line of text with the search matches highlighted.
+fn highlight(text: &str, search: Option<&Search>) -> Line<'static> {
+    let Some(search) = search else {
+        return Line::raw(text.to_string());
+    };
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for (start, end) in search.spans(text) {
+        spans.push(Span::raw(text[last..start].to_string()));
+        spans.push(Span::styled(text[start..end].to_string(), Style::new().black().on_yellow()));
+        last = end;
+    }
+    spans.push(Span::raw(text[last..].to_string()));
+    Line::from(spans)
+}
+
+/// Bottom line: the prompt while typing, otherwise the search state or key hints.
+fn draw_status(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
+    let label = Style::new().green().italic();
+    let mut spans = match (app.input_mode(), app.search()) {
+        (InputMode::Command, _) => {
+            let prompt = format!(":{}", app.prompt());
+            frame.set_cursor_position((area.x + prompt.chars().count() as u16, area.y));
+            vec![Span::raw(prompt)]
+        }
+        (InputMode::Search, _) => {
+            let prompt = format!("/{}", app.prompt());
+            // cursor right after the typed text
+            frame.set_cursor_position((area.x + prompt.chars().count() as u16, area.y));
+            vec![
+                Span::raw(prompt),
+                Span::raw(" ".repeat(3)),
+                Span::styled(format!("[{}, Tab to change]", app.search_mode()), label),
+            ]
+        }
+        (InputMode::Facets, _) => vec![Span::styled(
+            "Up/Down select value   Left/Right change field   Enter filter on value   Esc close",
+            label,
+        )],
+        (_, Some(search)) => vec![
+            Span::styled("Search: ", label),
+            Span::raw(format!("{} ({})", search.query(), search.mode())),
+            Span::raw(" ".repeat(3)),
+            Span::styled(
+                if app.filter_matches() { "filtering, f to highlight only" } else { "highlighting, n/N to jump, f to filter" },
+                label,
+            ),
+        ],
+        _ => vec![Span::styled(
+            "/ search   :filter   Enter details   t table/list   T timeline   b facets   s sort   o/O sort by column   x/X hide/show column   q quit",
+            label,
+        )],
+    };
+    if app.input_mode() == InputMode::Normal && let Some(filter) = app.filter() {
+        spans.push(Span::raw(" ".repeat(3)));
+        spans.push(Span::styled("Filter: ", label));
+        spans.push(Span::raw(filter.to_string()));
+    }
+    for error in [app.prompt_error(), app.last_error()].into_iter().flatten() {
+        spans.push(Span::raw(" ".repeat(3)));
+        spans.push(Span::styled(error.to_string(), Style::new().red()));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Shrinks the widest columns one character at a time until they fit in available.
+fn fit_widths(mut widths: Vec<usize>, available: usize) -> Vec<usize> {
+    const MIN_WIDTH: usize = 3;
+    while widths.iter().sum::<usize>() > available {
+        match widths.iter_mut().filter(|w| **w > MIN_WIDTH).max() {
+            Some(widest) => *widest -= 1,
+            None => break,
+        }
+    }
+    widths
+}
+
+/// Cuts value to width characters, ending with an ellipsis when something was cut.
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut cut: String = value.chars().take(width - 1).collect();
+    cut.push('…');
+    cut
 }
 
 fn draw_title3(frame: &mut Frame, app: &eventline::EventLine, area: Rect) {
     let title = app.title();
-    let text = vec![
+    let stats = app.loop_stats();
+    let mut counters = vec![
+        Span::styled("Global Counter: ", Style::new().green().italic()),
+        Span::raw(format!("{:>5}", app.global_counter())),
+        Span::raw(" ".repeat(5)),
+        Span::styled("Local Counter: ", Style::new().green().italic()),
+        Span::raw(format!("{:>5}", app.events_map_size())),
+        Span::raw(" ".repeat(5)),
+    ];
+    for severity in Severity::ALL {
+        let (r, g, b) = severity_color(severity);
+        counters.push(Span::styled(
+            format!("{}:{} ", severity, app.severity_count(severity)),
+            Style::new().fg(Color::Rgb(r, g, b)),
+        ));
+    }
+    if app.filter().is_some() || app.min_severity() > Severity::Trace {
+        counters.extend(vec![
+            Span::raw(" ".repeat(5)),
+            Span::styled("Filtered: ", Style::new().green().italic()),
+            Span::raw(format!("{} of {}", app.filtered_count(), app.events_map_size())),
+        ]);
+    }
+    if app.search().is_some() {
+        counters.extend(vec![
+            Span::raw(" ".repeat(5)),
+            Span::styled("Matching: ", Style::new().green().italic()),
+            Span::raw(format!("{} of {}", app.match_count(), app.events_map_size())),
+        ]);
+    }
+    // overflow counters only make sense for a bounded channel
+    if let Some(stats) = app.channel_stats().filter(|stats| stats.capacity.is_some()) {
+        counters.extend(vec![
+            Span::raw(" ".repeat(5)),
+            Span::styled("Dropped: ", Style::new().green().italic()),
+            Span::raw(format!("{:>5}", stats.dropped)),
+            Span::raw(" ".repeat(5)),
+            Span::styled("Coalesced: ", Style::new().green().italic()),
+            Span::raw(format!("{:>5}", stats.coalesced)),
+            Span::raw(" ".repeat(5)),
+            Span::styled("Queued: ", Style::new().green().italic()),
+            Span::raw(format!("{}/{} ({:?})", stats.queued, stats.capacity.unwrap_or(0), stats.policy)),
+        ]);
+    }
+    let mut last_update = vec![
+        Span::styled("Last update:", Style::new().green().italic()),
+        Span::raw(" ".repeat(5)),
+        Span::raw(app.last_update()),
+    ];
+    if app.disconnected() {
+        last_update.extend(vec![
+            Span::raw(" ".repeat(5)),
+            Span::styled("source disconnected, showing the last state", Style::new().yellow().bold()),
+        ]);
+    }
+    let mut text = vec![
         Line::from(vec![
             Span::styled("Current time", Style::new().green().italic()),
             Span::raw(" ".repeat(5)),
             Span::raw(chrono::Local::now().with_timezone(&chrono::Local).to_rfc3339()),
-        ]),
-        Line::from( vec![
-            Span::styled("Global Counter: ", Style::new().green().italic()),
-            Span::raw(format!("{:>5}", app.global_counter())),
             Span::raw(" ".repeat(5)),
-            Span::styled("Local Counter: ", Style::new().green().italic()),
-            Span::raw(format!("{:>5}", app.events_map_size())),
+            Span::styled("Applied: ", Style::new().green().italic()),
+            Span::raw(format!("{}/s", stats.apply_rate())),
+            Span::raw(" ".repeat(5)),
+            Span::styled("Frames: ", Style::new().green().italic()),
+            Span::raw(format!("{}/s (max {})", stats.frame_rate(), app.max_fps())),
+            Span::raw(" ".repeat(5)),
+            Span::styled("Frame time: ", Style::new().green().italic()),
+            Span::raw(format!("{:.1}ms", stats.frame_time().as_secs_f64() * 1000.0)),
         ]),
+        Line::from(counters),
+        Line::from(last_update),
         Line::from( vec![
-            Span::styled("Last update:", Style::new().green().italic()),
+            Span::styled("Sort:", Style::new().green().italic()),
+            Span::raw(" ".repeat(12)),
+            Span::raw(format!("{:<20}", app.sort().to_string())),
+            Span::raw(" ".repeat(5)),
+            Span::styled("Mode: ", Style::new().green().italic()),
+            if app.follow() {
+                Span::styled("follow (F to stop)", Style::new().light_green().bold())
+            } else {
+                Span::raw("browse (F to follow)")
+            },
             Span::raw(" ".repeat(5)),
-            Span::raw(format!("{}", app.last_update())),
+            Span::styled("Level: ", Style::new().green().italic()),
+            Span::raw(if app.min_severity() > Severity::Trace {
+                format!("{} and above (+/-)", app.min_severity())
+            } else {
+                String::from("all (+/- to hide)")
+            }),
         ]),
     ];
+    if !app.sources().is_empty() {
+        let mut sources = vec![Span::styled("Sources:", Style::new().green().italic()), Span::raw(" ".repeat(9))];
+        for (i, (name, status)) in app.sources().iter().enumerate() {
+            if i > 0 {
+                sources.push(Span::raw(", "));
+            }
+            sources.push(Span::raw(format!("{}: ", name)));
+            sources.push(Span::styled(status.clone(), source_style(status)));
+        }
+        text.push(Line::from(sources));
+    }
 
-    frame.render_widget(
-        Paragraph::new(text)
-            .block(Block::bordered().title(title))
-            .style(Style::new().white().on_black()),
-        area,
-    )
-}
+    // the update rate and the times of the events are drawn as sparklines between
+    // their label and their legend
+    let block = Block::bordered().title(title);
+    let inner = block.inner(area);
+    let graph_row = inner.y + text.len() as u16;
+    let graph_width = inner.width.saturating_sub(GRAPH_LABEL + GRAPH_LEGEND);
+    let per_second = app.rate().per_second();
+    let rate = activity::squeeze(&per_second, graph_width as usize);
+    let histogram = app.time_histogram(graph_width as usize);
+    let window = app.rate().window().as_secs();
+    let window = if window.is_multiple_of(60) { format!("{}m", window / 60) } else { format!("{}s", window) };
+    text.push(Line::from(vec![
+        Span::styled("Rate:", Style::new().green().italic()),
+        Span::raw(format!("{:<12}", format!(" {}/s", app.rate().last_rate()))),
+        Span::raw(" ".repeat(graph_width as usize)),
+        Span::raw(format!(
+            " last {}, peak {}/s",
+            window,
+            per_second.iter().max().copied().unwrap_or(0)
+        )),
+    ]));
+    text.push(Line::from(match &histogram {
+        Some(histogram) => vec![
+            Span::styled("Times:", Style::new().green().italic()),
+            Span::raw(format!("{:<11}", format!(" {} each", histogram.bucket))),
+            Span::raw(" ".repeat(graph_width as usize)),
+            Span::raw(format!(
+                " {} to {}",
+                histogram.start.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                histogram.end.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            )),
+        ],
+        None => vec![Span::styled("Times:", Style::new().green().italic())],
+    }));
 
-fn draw_title(frame: &mut Frame, app: &mut eventline::EventLine, area: Rect) {
-    let title = app.title();
-    frame.render_widget(
-        Paragraph::new(Text::from(title))
-            .block(Block::bordered().title(title))
-            .style(Style::new().white().on_black()),
-        area,
-    )
+    frame.render_widget(Paragraph::new(text).block(block).style(Style::new().white().on_black()), area);
+    if graph_width > 0 && graph_row + 1 < inner.y + inner.height {
+        let graph = |row| Rect::new(inner.x + GRAPH_LABEL, row, graph_width, 1);
+        frame.render_widget(Sparkline::default().data(&rate).style(Style::new().light_green()), graph(graph_row));
+        if let Some(histogram) = histogram {
+            let counts = activity::squeeze(&histogram.counts, graph_width as usize);
+            frame.render_widget(Sparkline::default().data(&counts).style(Style::new().cyan()), graph(graph_row + 1));
+        }
+    }
 }
-fn validate_terminal_size(size: Size) -> Result<Size, String> {
-    const MIN_HEIGHT: u16 = 15;
-    const MIN_WIDTH: u16 = 80;
 
-    if size.height < MIN_HEIGHT || size.width < MIN_WIDTH {
-        return Err(String::from("terminal too small"));
-    }
+/// Width of the labels before the header sparklines.
+const GRAPH_LABEL: u16 = 17;
+/// Width kept after the header sparklines for their legend.
+const GRAPH_LEGEND: u16 = 40;
 
-    Ok(size)
+/// A closed or failing source stands out.
+fn source_style(status: &str) -> Style {
+    if status.starts_with("closed") {
+        Style::new().yellow().bold()
+    } else if status.starts_with("error") {
+        Style::new().red().bold()
+    } else {
+        Style::new()
+    }
 }
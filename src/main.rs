@@ -1,96 +1,80 @@
-mod eventline;
-
-use crate::eventline::eventline::{Event, EventLine, Events};
-use std::thread;
+use eventline_rust::eventline::channel::{EventSender, OverflowPolicy, ShutdownHandle};
+use eventline_rust::eventline::eventline::{Event, EventLine, EventMessage};
+use eventline_rust::eventline::field::Field;
+use eventline_rust::eventline::severity::Severity;
+use eventline_rust::eventline::source::stdin::{STDIN, read_stdin};
+use eventline_rust::eventline::source::syslog::{self, SyslogOptions};
+use eventline_rust::eventline::source::tail::{TailOptions, tail_files};
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use fakeit::company::company;
 use fakeit::datetime::DateTime;
-use fakeit::{address, company, datetime, unique};
+use fakeit::{address, datetime};
 #[tokio::main]
 
 async fn main() -> Result<(), String> {
     //let log_events = log()?;
 
-    match EventLine::new(String::from("my title")) {
+    // `eventline-rust FILE...` follows the files, `--syslog-udp`/`--syslog-tcp` receive
+    // syslog messages, `some-service | eventline-rust` shows the piped lines,
+    // otherwise demo events are generated
+    let args = parse_args(std::env::args().skip(1))?;
+    let piped = !std::io::stdin().is_terminal();
+    let listening = args.syslog.udp.is_some() || args.syslog.tcp.is_some();
+    let title = if !args.files.is_empty() {
+        "tail"
+    } else if listening {
+        "syslog"
+    } else if piped {
+        STDIN
+    } else {
+        "my title"
+    };
+
+    match EventLine::new(String::from(title)) {
         Ok(mut ev) => {
             // ev.test(vec!["1".to_string(),"2".to_string(),"3".to_string(),"4".to_string(),"5".to_string(),"6".to_string(),"7".to_string(),"8".to_string(),"9".to_string(),"10".to_string()]);
-            // Create event channel using crossbeam
-            let event_sender = ev.create_event_channel();
+            // Create the event channel, the producer waits when the viewer falls behind
+            let event_sender = ev.create_bounded_event_channel(1024, OverflowPolicy::Block);
+            if let Some(max_fps) = args.max_fps {
+                ev.with_max_fps(max_fps);
+            }
+            ev.with_exit_on_disconnect(args.exit_on_disconnect);
+            let shutdown = ev.shutdown_handle();
 
             // Start the EventLine in a thread
             let handle = ev.start_in_thread();
             //    .map_err(|e| format!("Failed to start thread: {}", e))?;
 
-            let mut log_events = Events::<LogEvent> {
-                global_counter: 0,
-                last_update: "today".to_string(),
-                events_map: std::collections::HashMap::new(),
-            };
-
-            let mut log_events_test = Events::<dyn Event + Send> {
-                global_counter: 0,
-                last_update: "today".to_string(),
-                events_map: std::collections::HashMap::new(),
-            };
-
-            for i in 0..10 {
-                let log_key = format!("log{}", i);
-                let new_logged_event = LogEvent::new(format!("log message {}", i))?;
-                log_events.last_update = new_logged_event.id.clone();
-                log_events.global_counter += 1;
-                log_events.events_map.insert(
-                    log_key.clone(),
-                    Box::new(LogEvent::new(format!("log message {}", i))?),
-                );
-
-                // alternative
-                let new_logged_event_alt = LogEvent::new(format!("log message {}", i))?;
-                log_events_test.last_update = new_logged_event_alt.id.clone();
-                log_events_test.global_counter += 1;
-                log_events_test.events_map.insert(
-                    log_key.clone(),
-                    Box::new(LogEvent::new(format!("log alt message {}", i))?),
-                );
-
-                let cloned_events = log_events.clone();
-                // Create a new Events object with the expected type
-                let mut boxed_events = Events {
-                    global_counter: cloned_events.global_counter,
-                    last_update: cloned_events.last_update,
-                    events_map: std::collections::HashMap::new(),
-                };
-
-                // Convert each LogEvent to a boxed dyn Event
-                for (key, log_event) in cloned_events.events_map {
-                    boxed_events
-                        .events_map
-                        .insert(key, log_event as Box<dyn Event + Send>);
+            // failures are reported in the header
+            let produced = if !args.files.is_empty() || listening {
+                if listening {
+                    tokio::spawn(syslog::listen(args.syslog, event_sender.clone()));
                 }
-                tokio::select! {
-                    // Send the boxed events - use async block to make it a future
-                    _ = async {
-                        let _ = event_sender.send(Box::new(boxed_events));
-                        println!("Sent events");
-                        Ok::<_, std::io::Error>(())
-                    } => {
-                        // sending data
-                    }
-                    _ = tokio::time::sleep(Duration::from_millis(1000)) => {
-                        //println!("Sent events");
-                    }
+                if !args.files.is_empty() {
+                    tokio::spawn(tail_files(args.files, args.tail, event_sender.clone()));
                 }
-                // Alternative approach: just send outside the select
-                // tokio::select! {
-                //     _ = tokio::time::sleep(Duration::from_millis(1000)) => {
-                //         //println!("Sent events");
-                //     }
-                // }
-                // let _ = event_sender.send(Box::new(boxed_events));
+                // only the producers hold a sender, the viewer sees when they are all gone
+                drop(event_sender);
+                Ok(())
+            } else if piped {
+                tokio::spawn(read_stdin(args.tail.json, event_sender));
+                Ok(())
+            } else {
+                demo(event_sender, shutdown).await
+            };
+            // a failing producer ends the viewer, which gives the terminal back first
+            if let Err(e) = produced {
+                handle.abort();
+                let _ = handle.await;
+                return Err(e);
             }
-            println!("Waiting for task to complete...");
             match handle.await {
-                Ok(_) => println!("Task completed successfully"),
+                Ok(Ok(())) => println!("Task completed successfully"),
+                Ok(Err(e)) => return Err(e),
                 Err(e) => println!("Task failed with error: {}", e),
             }
         }
@@ -99,6 +83,87 @@ async fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// Sends demo events, one every second, until the viewer ends.
+async fn demo(event_sender: EventSender, mut shutdown: ShutdownHandle) -> Result<(), String> {
+    for i in 0..10 {
+        let log_key = format!("log{}", i);
+        let severity = Severity::ALL[i % Severity::ALL.len()];
+        let new_logged_event = LogEvent::new(format!("log message {}", i), severity)?;
+        // only the new event goes through the channel, the viewer keeps the others
+        if event_sender
+            .send(EventMessage::Upsert(log_key, Box::new(new_logged_event)))
+            .await
+            .is_err()
+        {
+            break;
+        }
+        tokio::select! {
+            _ = shutdown.wait() => break,
+            _ = tokio::time::sleep(Duration::from_millis(1000)) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Sources given on the command line.
+struct Args {
+    files: Vec<PathBuf>,
+    tail: TailOptions,
+    syslog: SyslogOptions,
+    max_fps: Option<u32>,
+    exit_on_disconnect: bool,
+}
+
+/// `[--from-start] [--offsets FILE] [--time-key KEY]... [--id-key KEY]
+/// [--syslog-udp ADDR] [--syslog-tcp ADDR] [--max-fps N] [--exit-on-disconnect] FILE...`.
+/// The JSON keys apply to stdin too, an address can be a port alone to listen on localhost.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut files = Vec::new();
+    let mut options = TailOptions::default();
+    let mut syslog = SyslogOptions::default();
+    let mut time_keys = Vec::new();
+    let mut max_fps = None;
+    let mut exit_on_disconnect = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--time-key" => time_keys.push(args.next().ok_or("--time-key needs a key")?),
+            "--id-key" => options.json.id_key = args.next().ok_or("--id-key needs a key")?,
+            "--from-start" => options.from_start = true,
+            "--offsets" => {
+                let path = args.next().ok_or("--offsets needs a file")?;
+                options.offsets = Some(PathBuf::from(path));
+            }
+            "--syslog-udp" => syslog.udp = Some(address(args.next().ok_or("--syslog-udp needs an address")?)?),
+            "--syslog-tcp" => syslog.tcp = Some(address(args.next().ok_or("--syslog-tcp needs an address")?)?),
+            "--exit-on-disconnect" => exit_on_disconnect = true,
+            "--max-fps" => {
+                let fps = args.next().ok_or("--max-fps needs a number")?;
+                max_fps = Some(fps.parse().map_err(|_| format!("bad frame rate {}", fps))?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if !time_keys.is_empty() {
+        options.json.time_keys = time_keys;
+    }
+    Ok(Args {
+        files,
+        tail: options,
+        syslog,
+        max_fps,
+        exit_on_disconnect,
+    })
+}
+
+/// Socket address, or port on localhost.
+fn address(arg: String) -> Result<SocketAddr, String> {
+    match arg.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from(([127, 0, 0, 1], port))),
+        Err(_) => arg.parse().map_err(|_| format!("bad address {}", arg)),
+    }
+}
+
 struct LogEvent {
     timestamp: chrono::DateTime<chrono::Utc>,
     message: String,
@@ -107,25 +172,10 @@ struct LogEvent {
     country: String,
     city: String,
     date: DateTime,
+    severity: Severity,
 }
 
 impl Event for LogEvent {
-    fn get_event_presentation(&self) -> String {
-        //format!("[{}] {}", self.timestamp, self.message)
-        format!(
-            "Event: {:<20} - Compnay: {:<25} - country: {:<25} - City: {:<20} - time: {:<29}",
-            self.id,
-            self.company,
-            self.country,
-            self.city,
-            chrono::DateTime::from_timestamp(self.date.secs, self.date.nsecs)
-                .unwrap()
-                .with_timezone(&chrono::Local)
-                .format("%Y-%m-%dT%H:%M:%S%:z")
-        )
-        //self.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S%:z"))
-    }
-
     fn get_event_time(&self) -> chrono::DateTime<chrono::Utc> {
         self.timestamp
     }
@@ -134,6 +184,25 @@ impl Event for LogEvent {
         self.id.clone()
     }
 
+    fn get_event_severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn get_event_fields(&self) -> Vec<Field> {
+        vec![
+            Field::new("id", self.id.as_str()),
+            Field::new("company", self.company.as_str()),
+            Field::new("country", self.country.as_str()),
+            Field::new("city", self.city.as_str()),
+            Field::new("message", self.message.as_str()),
+            Field::new("timestamp", self.timestamp),
+            Field::new(
+                "date",
+                chrono::DateTime::from_timestamp(self.date.secs, self.date.nsecs).unwrap(),
+            ),
+        ]
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -144,7 +213,7 @@ impl Event for LogEvent {
 }
 
 impl LogEvent {
-    fn new(message: String) -> Result<LogEvent, String> {
+    fn new(message: String, severity: Severity) -> Result<LogEvent, String> {
         let to_convert_timestamp = datetime::date();
         Ok(LogEvent {
             timestamp: chrono::DateTime::from_timestamp(
@@ -152,12 +221,13 @@ impl LogEvent {
                 to_convert_timestamp.nsecs,
             )
             .unwrap(),
-            message: message,
+            message,
             id: fakeit::unique::uuid_v4(),
             company: company(),
             country: fakeit::address::country(),
             city: address::city(),
             date: to_convert_timestamp,
+            severity,
         })
     }
 }
@@ -176,43 +246,7 @@ impl Clone for LogEvent {
                 secs: self.date.secs,
                 nsecs: self.date.nsecs,
             },
+            severity: self.severity,
         }
     }
 }
-
-impl Events<dyn Event + Send> {
-    pub fn clone_events(&self) -> Self {
-        let mut new_map = std::collections::HashMap::new();
-        for (key, event) in &self.events_map {
-            // We need to make a deep copy of the event
-            // This requires that each concrete Event type implements Clone
-            // or provides a way to duplicate itself
-
-            // Clone the key and dereference the Box to get access to the underlying Event
-            // Then create a new Box containing a clone or new instance of the event
-            if let Some(_concrete_event) = self.clone_event(event.as_ref()) {
-                new_map.insert(key.clone(), event.as_ref().clone_dyn());
-            }
-        }
-
-        Events {
-            global_counter: self.global_counter,
-            last_update: self.last_update.clone(),
-            events_map: new_map,
-        }
-    }
-
-    // Helper method to create clones of specific event types
-    fn clone_event(&self, event: &(dyn Event + Send)) -> Option<Box<dyn Event + Send>> {
-        // Use the as_any method to try downcasting to specific event types
-        // Then clone that specific type
-
-        // Example (you need to implement this for your specific event types):
-        if let Some(log_event) = event.as_any().downcast_ref::<LogEvent>() {
-            return Some(Box::new(log_event.clone()));
-        }
-
-        // For now, this is a placeholder - you must implement this for your specific event types
-        None
-    }
-}